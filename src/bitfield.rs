@@ -0,0 +1,243 @@
+use crate::{context::RobustContext, types::Word};
+use std::io;
+use tokio_modbus::{prelude::*, Address, Error as ModbusError, Result as ModbusResult};
+
+/// A holding register whose bits carry named flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    pub addr: Address,
+    pub flags: Vec<(&'static str, u8)>,
+}
+
+impl Bitfield {
+    pub fn new(addr: Address) -> Self {
+        Self {
+            addr,
+            flags: Vec::new(),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If `bit` is not below 16.
+    pub fn with_flag(mut self, name: &'static str, bit: u8) -> Self {
+        assert!(
+            bit < 16,
+            "flag {name} at bit {bit} does not fit into a register"
+        );
+        self.flags.push((name, bit));
+        self
+    }
+
+    /// `None` for unknown flags and for flags at bits past the register.
+    pub fn mask(&self, name: &str) -> Option<Word> {
+        self.flags
+            .iter()
+            .find(|(flag, _)| *flag == name)
+            .and_then(|(_, bit)| bit_mask(*bit))
+    }
+
+    /// State of every named flag, in definition order.
+    pub fn decode(&self, word: Word) -> Vec<(&'static str, bool)> {
+        self.flags
+            .iter()
+            .map(|(name, bit)| (*name, bit_mask(*bit).is_some_and(|mask| word & mask != 0)))
+            .collect()
+    }
+
+    /// Names of the flags that are set.
+    pub fn active(&self, word: Word) -> Vec<&'static str> {
+        self.decode(word)
+            .into_iter()
+            .filter_map(|(name, set)| set.then_some(name))
+            .collect()
+    }
+
+    /// Builds the `(and_mask, or_mask)` pair for a masked write that only touches `changes`.
+    pub fn encode(&self, changes: &[(&str, bool)]) -> io::Result<(Word, Word)> {
+        changes
+            .iter()
+            .try_fold((Word::MAX, 0), |(and_mask, or_mask), (name, set)| {
+                let mask = self.mask(name).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown flag {name}"))
+                })?;
+                Ok(match set {
+                    true => (and_mask & !mask, or_mask | mask),
+                    false => (and_mask & !mask, or_mask & !mask),
+                })
+            })
+    }
+}
+
+fn bit_mask(bit: u8) -> Option<Word> {
+    1_u16.checked_shl(bit.into())
+}
+
+/// A holding register whose value selects one of several named modes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumRegister {
+    pub addr: Address,
+    pub variants: Vec<(Word, &'static str)>,
+}
+
+impl EnumRegister {
+    pub fn new(addr: Address) -> Self {
+        Self {
+            addr,
+            variants: Vec::new(),
+        }
+    }
+
+    pub fn with_variant(mut self, value: Word, name: &'static str) -> Self {
+        self.variants.push((value, name));
+        self
+    }
+
+    pub fn decode(&self, word: Word) -> Option<&'static str> {
+        self.variants
+            .iter()
+            .find(|(value, _)| *value == word)
+            .map(|(_, name)| *name)
+    }
+
+    pub fn encode(&self, name: &str) -> io::Result<Word> {
+        self.variants
+            .iter()
+            .find(|(_, variant)| *variant == name)
+            .map(|(value, _)| *value)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown variant {name}"),
+                )
+            })
+    }
+}
+
+/// A Rust enum stored in a single register.
+pub trait RegisterEnum: Sized {
+    fn from_word(word: Word) -> Option<Self>;
+    fn to_word(&self) -> Word;
+}
+
+impl RobustContext {
+    pub async fn read_bitfield(
+        &mut self,
+        bitfield: &Bitfield,
+    ) -> ModbusResult<Vec<(&'static str, bool)>> {
+        self.read_holding_registers(bitfield.addr, 1)
+            .await
+            .map(|res| res.map(|words| bitfield.decode(words[0])))
+    }
+
+    /// Sets or clears named flags with a single masked write, leaving all other bits alone.
+    pub async fn write_flags(
+        &mut self,
+        bitfield: &Bitfield,
+        changes: &[(&str, bool)],
+    ) -> ModbusResult<()> {
+        let (and_mask, or_mask) = bitfield.encode(changes).map_err(ModbusError::Transport)?;
+        self.masked_write_register(bitfield.addr, and_mask, or_mask)
+            .await
+    }
+
+    pub async fn write_flag(
+        &mut self,
+        bitfield: &Bitfield,
+        name: &str,
+        set: bool,
+    ) -> ModbusResult<()> {
+        self.write_flags(bitfield, &[(name, set)]).await
+    }
+
+    /// Reads a named mode, `None` if the value is not a known variant.
+    pub async fn read_enum_register(
+        &mut self,
+        register: &EnumRegister,
+    ) -> ModbusResult<Option<&'static str>> {
+        self.read_holding_registers(register.addr, 1)
+            .await
+            .map(|res| res.map(|words| register.decode(words[0])))
+    }
+
+    pub async fn write_enum_register(
+        &mut self,
+        register: &EnumRegister,
+        name: &str,
+    ) -> ModbusResult<()> {
+        let word = register.encode(name).map_err(ModbusError::Transport)?;
+        self.write_single_register(register.addr, word).await
+    }
+
+    /// Reads a typed enum, `None` if the value is not a known variant.
+    pub async fn read_enum<E: RegisterEnum>(&mut self, addr: Address) -> ModbusResult<Option<E>> {
+        self.read_holding_registers(addr, 1)
+            .await
+            .map(|res| res.map(|words| E::from_word(words[0])))
+    }
+
+    pub async fn write_enum<E: RegisterEnum>(
+        &mut self,
+        addr: Address,
+        value: &E,
+    ) -> ModbusResult<()> {
+        self.write_single_register(addr, value.to_word()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> Bitfield {
+        Bitfield::new(0)
+            .with_flag("running", 0)
+            .with_flag("fault", 3)
+            .with_flag("remote", 15)
+    }
+
+    #[test]
+    fn decodes_flags() {
+        let status = status();
+        assert_eq!(
+            status.decode(0x8001),
+            vec![("running", true), ("fault", false), ("remote", true)]
+        );
+        assert_eq!(status.active(0x0008), vec!["fault"]);
+    }
+
+    #[test]
+    fn encodes_masked_write() {
+        let (and_mask, or_mask) = status()
+            .encode(&[("fault", true), ("running", false)])
+            .unwrap();
+        assert_eq!(and_mask, !0x0009);
+        assert_eq!(or_mask, 0x0008);
+        assert!(status().encode(&[("missing", true)]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_bit_past_register() {
+        let _ = Bitfield::new(0).with_flag("typo", 16);
+    }
+
+    #[test]
+    fn ignores_bit_past_register_set_directly() {
+        let mut bitfield = status();
+        bitfield.flags.push(("typo", 16));
+        assert_eq!(bitfield.mask("typo"), None);
+        assert!(!bitfield.decode(0xffff)[3].1);
+    }
+
+    #[test]
+    fn enum_register_round_trip() {
+        let mode = EnumRegister::new(0)
+            .with_variant(0, "off")
+            .with_variant(2, "auto");
+        assert_eq!(mode.decode(2), Some("auto"));
+        assert_eq!(mode.decode(1), None);
+        assert_eq!(mode.encode("off").unwrap(), 0);
+        assert!(mode.encode("manual").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::context::RobustContext;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Fails requests fast while the device is known to be unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    /// Failed requests in a row that open the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a single probe request is let through.
+    pub open_for: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent.
+    Closed,
+    /// Requests fail immediately with [`crate::prelude::RobustError::CircuitOpen`].
+    Open,
+    /// A single probe request is in flight, everything else fails immediately.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Inner {
+    config: Option<CircuitBreaker>,
+    state: State,
+}
+
+/// Circuit shared by all clones of a context.
+#[derive(Debug)]
+pub(crate) struct Breaker {
+    inner: Mutex<Inner>,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                config: None,
+                state: State::Closed { failures: 0 },
+            }),
+        }
+    }
+}
+
+impl Breaker {
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_config(&self, config: Option<CircuitBreaker>) {
+        let mut inner = self.inner();
+        inner.config = config;
+        inner.state = State::Closed { failures: 0 };
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match self.inner().state {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Lets a request through, or returns `None` if the circuit is open.
+    pub fn admit(&self) -> Option<Admission<'_>> {
+        let mut inner = self.inner();
+        let probe = match inner.state {
+            _ if inner.config.is_none() => false,
+            State::Closed { .. } => false,
+            State::Open { until } if until <= Instant::now() => {
+                info!("circuit half-open, probing");
+                inner.state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => return None,
+        };
+
+        Some(Admission {
+            breaker: self,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn open(inner: &mut Inner, config: CircuitBreaker) {
+        inner.state = State::Open {
+            until: Instant::now() + config.open_for,
+        };
+    }
+}
+
+/// A request let through the circuit, whose outcome has to be recorded.
+pub(crate) struct Admission<'a> {
+    breaker: &'a Breaker,
+    probe: bool,
+    recorded: bool,
+}
+
+impl Admission<'_> {
+    /// Records whether the device responded, with data or an exception.
+    pub fn record(mut self, success: bool) {
+        self.recorded = true;
+        let mut inner = self.breaker.inner();
+        let Some(config) = inner.config else {
+            return;
+        };
+
+        match (&mut inner.state, success) {
+            (State::HalfOpen, true) if self.probe => {
+                info!("circuit closed");
+                inner.state = State::Closed { failures: 0 };
+            }
+            (State::HalfOpen, false) if self.probe => Breaker::open(&mut inner, config),
+            (State::Closed { failures }, true) => *failures = 0,
+            (State::Closed { failures }, false) => {
+                *failures += 1;
+                if *failures >= config.failure_threshold {
+                    warn!("circuit opened after {} failures", failures);
+                    Breaker::open(&mut inner, config);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        // A probe that was cancelled lets the next request probe instead.
+        if self.probe && !self.recorded {
+            let mut inner = self.breaker.inner();
+            if let State::HalfOpen = inner.state {
+                inner.state = State::Open {
+                    until: Instant::now(),
+                };
+            }
+        }
+    }
+}
+
+impl RobustContext {
+    /// Enables the circuit breaker for this context and all its clones, `None` disables
+    /// it.
+    pub fn set_circuit_breaker(&self, config: Option<CircuitBreaker>) {
+        self.breaker.set_config(config);
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_for: Duration) -> Breaker {
+        let breaker = Breaker::default();
+        breaker.set_config(Some(CircuitBreaker {
+            failure_threshold: 2,
+            open_for,
+        }));
+        breaker
+    }
+
+    fn fail(breaker: &Breaker) {
+        breaker.admit().unwrap().record(false);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let breaker = Breaker::default();
+        for _ in 0..10 {
+            fail(&breaker);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(Duration::from_secs(60));
+        fail(&breaker);
+        breaker.admit().unwrap().record(true);
+        fail(&breaker);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        fail(&breaker);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.admit().is_none());
+    }
+
+    #[test]
+    fn probe_closes_or_reopens() {
+        let breaker = breaker(Duration::ZERO);
+        fail(&breaker);
+        fail(&breaker);
+
+        let probe = breaker.admit().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.admit().is_none());
+        probe.record(false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        breaker.admit().unwrap().record(true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn cancelled_probe_lets_next_request_probe() {
+        let breaker = breaker(Duration::ZERO);
+        fail(&breaker);
+        fail(&breaker);
+
+        drop(breaker.admit().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.admit().is_some());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    context::RobustContext,
+    types::{Coil, Word},
+};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio_modbus::{prelude::*, Address, Quantity, Result as ModbusResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Good,
+    /// Older than the requested max age while the connection is up.
+    Stale,
+    /// The connection to the device is currently down.
+    CommFailure,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cached<T> {
+    pub value: T,
+    /// Time of the oldest read contributing to `value`.
+    pub timestamp: SystemTime,
+    pub quality: Quality,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry<T> {
+    value: T,
+    read_at: Instant,
+    timestamp: SystemTime,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    coils: BTreeMap<Address, Entry<Coil>>,
+    discrete_inputs: BTreeMap<Address, Entry<Coil>>,
+    holding_registers: BTreeMap<Address, Entry<Word>>,
+    input_registers: BTreeMap<Address, Entry<Word>>,
+}
+
+fn store<T: Copy>(table: &mut BTreeMap<Address, Entry<T>>, addr: Address, values: &[T]) {
+    let (read_at, timestamp) = (Instant::now(), SystemTime::now());
+    for (offset, value) in (0..=Address::MAX).zip(values) {
+        let Some(addr) = addr.checked_add(offset) else {
+            break;
+        };
+        table.insert(
+            addr,
+            Entry {
+                value: *value,
+                read_at,
+                timestamp,
+            },
+        );
+    }
+}
+
+/// Last known values of everything read through a [`RobustContext`].
+#[derive(Debug)]
+pub struct RegisterCache {
+    connected: Arc<AtomicBool>,
+    tables: Mutex<Tables>,
+}
+
+impl RegisterCache {
+    pub(crate) fn new(connected: Arc<AtomicBool>) -> Self {
+        Self {
+            connected,
+            tables: Mutex::default(),
+        }
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn store_coils(&self, addr: Address, coils: &[Coil]) {
+        store(&mut self.tables().coils, addr, coils);
+    }
+
+    pub(crate) fn store_discrete_inputs(&self, addr: Address, coils: &[Coil]) {
+        store(&mut self.tables().discrete_inputs, addr, coils);
+    }
+
+    pub(crate) fn store_holding_registers(&self, addr: Address, words: &[Word]) {
+        store(&mut self.tables().holding_registers, addr, words);
+    }
+
+    pub(crate) fn store_input_registers(&self, addr: Address, words: &[Word]) {
+        store(&mut self.tables().input_registers, addr, words);
+    }
+
+    pub(crate) fn invalidate_holding_register(&self, addr: Address) {
+        self.tables().holding_registers.remove(&addr);
+    }
+
+    fn lookup<T: Copy>(
+        &self,
+        table: &BTreeMap<Address, Entry<T>>,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> Option<Cached<Vec<T>>> {
+        let entries = (0..cnt)
+            .map(|offset| table.get(&addr.checked_add(offset)?).copied())
+            .collect::<Option<Vec<_>>>()?;
+        let oldest = entries.iter().min_by_key(|entry| entry.read_at)?;
+
+        let quality = if !self.connected.load(Ordering::Relaxed) {
+            Quality::CommFailure
+        } else if oldest.read_at.elapsed() > max_age {
+            Quality::Stale
+        } else {
+            Quality::Good
+        };
+
+        Some(Cached {
+            value: entries.iter().map(|entry| entry.value).collect(),
+            timestamp: oldest.timestamp,
+            quality,
+        })
+    }
+
+    pub fn coils(
+        &self,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> Option<Cached<Vec<Coil>>> {
+        self.lookup(&self.tables().coils, addr, cnt, max_age)
+    }
+
+    pub fn discrete_inputs(
+        &self,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> Option<Cached<Vec<Coil>>> {
+        self.lookup(&self.tables().discrete_inputs, addr, cnt, max_age)
+    }
+
+    pub fn holding_registers(
+        &self,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> Option<Cached<Vec<Word>>> {
+        self.lookup(&self.tables().holding_registers, addr, cnt, max_age)
+    }
+
+    pub fn input_registers(
+        &self,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> Option<Cached<Vec<Word>>> {
+        self.lookup(&self.tables().input_registers, addr, cnt, max_age)
+    }
+}
+
+impl RobustContext {
+    /// Starts caching every successful read of this context and all its clones. Returns
+    /// the cache, which can be shared with readers that do not own the context.
+    pub fn enable_cache(&self) -> Arc<RegisterCache> {
+        self.cache
+            .get_or_init(|| Arc::new(RegisterCache::new(self.connected.clone())))
+            .clone()
+    }
+
+    pub fn cache(&self) -> Option<Arc<RegisterCache>> {
+        self.cache.get().cloned()
+    }
+
+    /// Serves holding registers from the cache if they are younger than `max_age`, reads
+    /// them from the device otherwise.
+    ///
+    /// If the device cannot be reached, the last known values are returned with their
+    /// quality flag instead of the transport error.
+    pub async fn read_cached(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+        max_age: Duration,
+    ) -> ModbusResult<Cached<Vec<Word>>> {
+        let cache = self.enable_cache();
+        if let Some(cached) = cache
+            .holding_registers(addr, cnt, max_age)
+            .filter(|cached| cached.quality == Quality::Good)
+        {
+            return Ok(Ok(cached));
+        }
+
+        match self.read_holding_registers(addr, cnt).await {
+            Ok(res) => Ok(res.map(|words| Cached {
+                value: words,
+                timestamp: SystemTime::now(),
+                quality: Quality::Good,
+            })),
+            Err(e) => cache.holding_registers(addr, cnt, max_age).map(Ok).ok_or(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(connected: bool) -> RegisterCache {
+        RegisterCache::new(Arc::new(AtomicBool::new(connected)))
+    }
+
+    #[test]
+    fn lookup_needs_every_address() {
+        let cache = cache(true);
+        cache.store_holding_registers(10, &[1, 2]);
+        let cached = cache.holding_registers(10, 2, Duration::MAX).unwrap();
+        assert_eq!(cached.value, vec![1, 2]);
+        assert_eq!(cached.quality, Quality::Good);
+        assert!(cache.holding_registers(10, 3, Duration::MAX).is_none());
+        assert!(cache.input_registers(10, 1, Duration::MAX).is_none());
+    }
+
+    #[test]
+    fn quality_reflects_age_and_connection() {
+        let cache = cache(true);
+        cache.store_coils(0, &[true]);
+        let cached = cache.coils(0, 1, Duration::ZERO).unwrap();
+        assert_eq!(cached.quality, Quality::Stale);
+
+        cache.connected.store(false, Ordering::Relaxed);
+        let cached = cache.coils(0, 1, Duration::MAX).unwrap();
+        assert_eq!(cached.quality, Quality::CommFailure);
+    }
+
+    #[test]
+    fn store_stops_at_last_address() {
+        let cache = cache(true);
+        cache.store_holding_registers(65_534, &[1, 2, 3]);
+        let cached = cache.holding_registers(65_534, 2, Duration::MAX).unwrap();
+        assert_eq!(cached.value, vec![1, 2]);
+        assert!(cache.holding_registers(0, 1, Duration::MAX).is_none());
+    }
+
+    #[tokio::test]
+    async fn enabled_for_earlier_clones() {
+        let ctx = RobustContext::new("127.0.0.1:502", Slave(1)).await.unwrap();
+        let clone = ctx.clone();
+        let cache = ctx.enable_cache();
+        assert!(clone
+            .cache()
+            .is_some_and(|shared| Arc::ptr_eq(&shared, &cache)));
+    }
+
+    #[test]
+    fn invalidate_drops_entry() {
+        let cache = cache(true);
+        cache.store_holding_registers(1, &[7]);
+        cache.invalidate_holding_register(1);
+        assert!(cache.holding_registers(1, 1, Duration::MAX).is_none());
+    }
+}
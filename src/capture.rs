@@ -0,0 +1,438 @@
+//! Capture of the raw traffic to a pcap file.
+//!
+//! Traffic is written as IP packets with synthetic TCP headers, so Wireshark can dissect
+//! the Modbus/TCP ADUs (use "Decode As" if the device does not listen on port 502).
+//! Connects, failed connects and closed connections are written as SYN, RST and FIN
+//! segments. This crate only speaks Modbus/TCP, so there is no RTU framing to capture.
+
+use crate::context::RobustContext;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tracing::warn;
+
+/// Raw IPv4 or IPv6, told apart by the version field.
+const LINKTYPE_RAW: u32 = 101;
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+const PSH: u8 = 0x08;
+const ACK: u8 = 0x10;
+
+/// A pcap file that the traffic of a [`RobustContext`] is written to.
+///
+/// Records are written by a thread of their own, so capturing does not block the runtime.
+/// Dropping the capture waits for the pending records to be written.
+pub struct Capture {
+    records: Option<mpsc::Sender<Vec<u8>>>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+impl Capture {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Writes the pcap header to `writer`.
+    pub fn new(mut writer: impl Write + Send + 'static) -> io::Result<Self> {
+        writer.write_all(&0xa1b2_c3d4_u32.to_le_bytes())?;
+        writer.write_all(&2_u16.to_le_bytes())?;
+        writer.write_all(&4_u16.to_le_bytes())?;
+        writer.write_all(&0_i32.to_le_bytes())?;
+        writer.write_all(&0_u32.to_le_bytes())?;
+        writer.write_all(&65535_u32.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+        writer.flush()?;
+
+        let (records, receiver) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("modbus-capture".into())
+            .spawn(move || write_records(writer, receiver))?;
+        Ok(Self {
+            records: Some(records),
+            writer: Some(writer),
+        })
+    }
+
+    fn record(&self, packet: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let len = packet.len() as u32;
+
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(packet);
+        if let Some(records) = &self.records {
+            let _ = records.send(record);
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.records = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes records as they come in, flushing whenever none are left.
+fn write_records(mut writer: impl Write, records: mpsc::Receiver<Vec<u8>>) {
+    while let Ok(record) = records.recv() {
+        let res = std::iter::once(record)
+            .chain(records.try_iter())
+            .try_for_each(|record| writer.write_all(&record))
+            .and_then(|()| writer.flush());
+        if let Err(e) = res {
+            warn!("could not write capture: {}", e);
+        }
+    }
+}
+
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = 0_u32;
+    for chunk in chunks {
+        for pair in chunk.chunks(2) {
+            let word = u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]);
+            sum += u32::from(word);
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// One TCP connection as seen in the capture.
+#[derive(Debug)]
+struct Flow {
+    capture: Arc<Capture>,
+    local: SocketAddr,
+    peer: SocketAddr,
+    local_seq: u32,
+    peer_seq: u32,
+    closed: bool,
+}
+
+impl Flow {
+    fn new(capture: Arc<Capture>, local: SocketAddr, peer: SocketAddr) -> Self {
+        Self {
+            capture,
+            local,
+            peer,
+            local_seq: 0,
+            peer_seq: 0,
+            closed: false,
+        }
+    }
+
+    fn segment(&mut self, from_local: bool, flags: u8, payload: &[u8]) {
+        let (src, dst, seq, ack) = if from_local {
+            (self.local, self.peer, self.local_seq, self.peer_seq)
+        } else {
+            (self.peer, self.local, self.peer_seq, self.local_seq)
+        };
+
+        let mut tcp = Vec::with_capacity(20 + payload.len());
+        tcp.extend_from_slice(&src.port().to_be_bytes());
+        tcp.extend_from_slice(&dst.port().to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&if flags & ACK != 0 { ack } else { 0 }.to_be_bytes());
+        tcp.extend_from_slice(&[5 << 4, flags]);
+        tcp.extend_from_slice(&u16::MAX.to_be_bytes());
+        tcp.extend_from_slice(&[0, 0, 0, 0]);
+        tcp.extend_from_slice(payload);
+        let tcp_len = tcp.len() as u16;
+
+        let packet = match (src.ip(), dst.ip()) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                let pseudo = [
+                    &src.octets()[..],
+                    &dst.octets(),
+                    &[0, 6],
+                    &tcp_len.to_be_bytes(),
+                ];
+                let sum = checksum(&[&pseudo.concat(), &tcp]);
+                tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+
+                let mut ip = Vec::with_capacity(20 + tcp.len());
+                ip.extend_from_slice(&[0x45, 0]);
+                ip.extend_from_slice(&(20 + tcp_len).to_be_bytes());
+                ip.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+                ip.extend_from_slice(&src.octets());
+                ip.extend_from_slice(&dst.octets());
+                let sum = checksum(&[&ip]);
+                ip[10..12].copy_from_slice(&sum.to_be_bytes());
+                ip.extend_from_slice(&tcp);
+                ip
+            }
+            (src, dst) => {
+                let src = to_ipv6(src).octets();
+                let dst = to_ipv6(dst).octets();
+                let pseudo = [
+                    &src[..],
+                    &dst,
+                    &u32::from(tcp_len).to_be_bytes(),
+                    &[0, 0, 0, 6],
+                ];
+                let sum = checksum(&[&pseudo.concat(), &tcp]);
+                tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+
+                let mut ip = Vec::with_capacity(40 + tcp.len());
+                ip.extend_from_slice(&[0x60, 0, 0, 0]);
+                ip.extend_from_slice(&tcp_len.to_be_bytes());
+                ip.extend_from_slice(&[6, 64]);
+                ip.extend_from_slice(&src);
+                ip.extend_from_slice(&dst);
+                ip.extend_from_slice(&tcp);
+                ip
+            }
+        };
+        self.capture.record(&packet);
+
+        let advance = payload.len() as u32 + u32::from(flags & (SYN | FIN) != 0);
+        if from_local {
+            self.local_seq = self.local_seq.wrapping_add(advance);
+        } else {
+            self.peer_seq = self.peer_seq.wrapping_add(advance);
+        }
+    }
+
+    fn handshake(&mut self) {
+        self.segment(true, SYN, &[]);
+        self.segment(false, SYN | ACK, &[]);
+        self.segment(true, ACK, &[]);
+    }
+
+    fn close(&mut self, from_local: bool, flags: u8) {
+        if !self.closed {
+            self.closed = true;
+            self.segment(from_local, flags, &[]);
+        }
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+/// Records a connect attempt that was refused or timed out.
+pub(crate) fn record_failed_connect(capture: Arc<Capture>, peer: SocketAddr) {
+    let local = match peer {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    };
+    let mut flow = Flow::new(capture, local, peer);
+    flow.segment(true, SYN, &[]);
+    flow.close(false, RST | ACK);
+}
+
+/// A TCP stream that records everything passing through it.
+#[derive(Debug)]
+pub(crate) struct CaptureStream {
+    stream: TcpStream,
+    flow: Flow,
+}
+
+impl CaptureStream {
+    pub fn new(stream: TcpStream, capture: Arc<Capture>) -> io::Result<Self> {
+        let mut flow = Flow::new(capture, stream.local_addr()?, stream.peer_addr()?);
+        flow.handshake();
+        Ok(Self { stream, flow })
+    }
+}
+
+impl AsyncRead for CaptureStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut this.stream).poll_read(cx, buf);
+        match &res {
+            Poll::Ready(Ok(())) if buf.filled().len() == filled => {
+                this.flow.close(false, FIN | ACK)
+            }
+            Poll::Ready(Ok(())) => this.flow.segment(false, PSH | ACK, &buf.filled()[filled..]),
+            Poll::Ready(Err(_)) => this.flow.close(false, RST | ACK),
+            Poll::Pending => {}
+        }
+        res
+    }
+}
+
+impl AsyncWrite for CaptureStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_write(cx, buf);
+        match &res {
+            Poll::Ready(Ok(n)) => this.flow.segment(true, PSH | ACK, &buf[..*n]),
+            Poll::Ready(Err(_)) => this.flow.close(true, RST | ACK),
+            Poll::Pending => {}
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_shutdown(cx);
+        if let Poll::Ready(Ok(())) = res {
+            this.flow.close(true, FIN | ACK);
+        }
+        res
+    }
+}
+
+impl Drop for CaptureStream {
+    fn drop(&mut self) {
+        self.flow.close(true, FIN | ACK);
+    }
+}
+
+impl RobustContext {
+    /// Writes all traffic of this context and its clones to `capture`, starting with the
+    /// next connect. `None` stops capturing.
+    pub fn set_capture(&self, capture: Option<Capture>) {
+        *self.capture.lock().unwrap_or_else(|e| e.into_inner()) = capture.map(Arc::new);
+    }
+
+    pub(crate) fn capture(&self) -> Option<Arc<Capture>> {
+        self.capture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Captures the packets written by `f`, after checking the pcap framing.
+    fn capture(f: impl FnOnce(Arc<Capture>)) -> Vec<Vec<u8>> {
+        let buffer = Buffer::default();
+        f(Arc::new(Capture::new(buffer.clone()).unwrap()));
+        let file = buffer.0.lock().unwrap().clone();
+
+        let u32_at = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), 0xa1b2_c3d4);
+        assert_eq!(&file[4..8], &[2, 0, 4, 0]);
+        assert_eq!(u32_at(16), 65535);
+        assert_eq!(u32_at(20), LINKTYPE_RAW);
+
+        let mut packets = Vec::new();
+        let mut at = 24;
+        while at < file.len() {
+            let len = u32_at(at + 8) as usize;
+            assert_eq!(u32_at(at + 12) as usize, len);
+            packets.push(file[at + 16..at + 16 + len].to_vec());
+            at += 16 + len;
+        }
+        assert_eq!(at, file.len());
+        packets
+    }
+
+    fn ipv4_flow(capture: Arc<Capture>) -> Flow {
+        let local = "10.0.0.1:50000".parse().unwrap();
+        let peer = "10.0.0.2:502".parse().unwrap();
+        Flow::new(capture, local, peer)
+    }
+
+    #[test]
+    fn writes_valid_ipv4_segments() {
+        let packets = capture(|capture| {
+            let mut flow = ipv4_flow(capture);
+            flow.handshake();
+            flow.segment(true, PSH | ACK, &[1, 2, 3]);
+            flow.close(true, FIN | ACK);
+        });
+        assert_eq!(packets.len(), 5);
+
+        for packet in &packets {
+            let (ip, tcp) = packet.split_at(20);
+            assert_eq!(ip[0], 0x45);
+            assert_eq!(
+                usize::from(u16::from_be_bytes([ip[2], ip[3]])),
+                packet.len()
+            );
+            assert_eq!(checksum(&[ip]), 0);
+            let pseudo = [&ip[12..20], &[0, 6], &(tcp.len() as u16).to_be_bytes()].concat();
+            assert_eq!(checksum(&[&pseudo, tcp]), 0);
+        }
+
+        let tcp = &packets[3][20..];
+        assert_eq!(u16::from_be_bytes([tcp[0], tcp[1]]), 50000);
+        assert_eq!(u16::from_be_bytes([tcp[2], tcp[3]]), 502);
+        assert_eq!(u32::from_be_bytes(tcp[4..8].try_into().unwrap()), 1);
+        assert_eq!(tcp[13], PSH | ACK);
+        assert_eq!(&tcp[20..], &[1, 2, 3]);
+        assert_eq!(packets[4][20 + 13], FIN | ACK);
+    }
+
+    #[test]
+    fn writes_valid_ipv6_segments() {
+        let packets = capture(|capture| {
+            let peer = "[::1]:502".parse().unwrap();
+            record_failed_connect(capture, peer);
+        });
+        assert_eq!(packets.len(), 2);
+
+        for packet in &packets {
+            let (ip, tcp) = packet.split_at(40);
+            assert_eq!(ip[0] >> 4, 6);
+            assert_eq!(usize::from(u16::from_be_bytes([ip[4], ip[5]])), tcp.len());
+            let len = (tcp.len() as u32).to_be_bytes();
+            let pseudo = [&ip[8..40], &len, &[0, 0, 0, 6]].concat();
+            assert_eq!(checksum(&[&pseudo, tcp]), 0);
+        }
+        assert_eq!(packets[1][40 + 13], RST | ACK);
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use tokio_modbus::{Address, Quantity};
+
+/// Maximum quantities per request. Defaults are the limits of the Modbus specification,
+/// lower them for devices with smaller buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PduLimits {
+    pub read_coils: Quantity,
+    pub read_registers: Quantity,
+    pub write_coils: Quantity,
+    pub write_registers: Quantity,
+}
+
+impl Default for PduLimits {
+    fn default() -> Self {
+        Self {
+            read_coils: 2000,
+            read_registers: 125,
+            write_coils: 1968,
+            write_registers: 123,
+        }
+    }
+}
+
+/// Fails if `cnt` items starting at `addr` run past the last address.
+pub(crate) fn check_range(addr: Address, cnt: usize) -> io::Result<()> {
+    if usize::from(addr) + cnt > usize::from(Address::MAX) + 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} items at address {} exceed the address space", cnt, addr),
+        ));
+    }
+    Ok(())
+}
+
+/// Splits `cnt` items starting at `addr` into requests of at most `max` items.
+///
+/// An empty request is passed through as is, so the device still gets to reject it.
+pub(crate) fn chunks(
+    addr: Address,
+    cnt: Quantity,
+    max: Quantity,
+) -> io::Result<impl Iterator<Item = (Address, Quantity)>> {
+    check_range(addr, cnt.into())?;
+    let max = max.max(1);
+    Ok((0..cnt.div_ceil(max).max(1)).map(move |i| {
+        let offset = i * max;
+        (addr + offset, max.min(cnt - offset))
+    }))
+}
+
+/// Splits `data` into slices of at most `max` items, paired with their start address.
+pub(crate) fn slices<T>(
+    addr: Address,
+    data: &[T],
+    max: Quantity,
+) -> io::Result<impl Iterator<Item = (Address, &[T])>> {
+    check_range(addr, data.len())?;
+    let max = usize::from(max.max(1));
+    Ok((0..data.len().div_ceil(max).max(1)).map(move |i| {
+        let offset = i * max;
+        let end = data.len().min(offset + max);
+        (addr + offset as Address, &data[offset..end])
+    }))
+}
+
+/// Groups sorted, distinct addresses into contiguous ranges of at most `max` items.
+pub(crate) fn runs(
+    addrs: impl IntoIterator<Item = Address>,
+    max: Quantity,
+) -> Vec<(Address, Quantity)> {
+    let max = max.max(1);
+    let mut runs: Vec<(Address, Quantity)> = Vec::new();
+    for addr in addrs {
+        match runs.last_mut() {
+            Some((start, cnt)) if *cnt < max && start.checked_add(*cnt) == Some(addr) => *cnt += 1,
+            _ => runs.push((addr, 1)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_split_at_limit() {
+        let chunks = chunks(10, 300, 125).unwrap().collect::<Vec<_>>();
+        assert_eq!(chunks, vec![(10, 125), (135, 125), (260, 50)]);
+    }
+
+    #[test]
+    fn chunks_pass_empty_request_through() {
+        let chunks = chunks(10, 0, 125).unwrap().collect::<Vec<_>>();
+        assert_eq!(chunks, vec![(10, 0)]);
+    }
+
+    #[test]
+    fn chunks_reach_last_address() {
+        let chunks = chunks(65_400, 136, 125).unwrap().collect::<Vec<_>>();
+        assert_eq!(chunks, vec![(65_400, 125), (65_525, 11)]);
+    }
+
+    #[test]
+    fn chunks_reject_wrap() {
+        let err = chunks(65_400, 137, 125).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn slices_reach_last_address() {
+        let data = [0_u16; 3];
+        let split = slices(65_533, &data, 2).unwrap().collect::<Vec<_>>();
+        assert_eq!(split, vec![(65_533, &data[..2]), (65_535, &data[2..])]);
+        assert!(slices(65_534, &data, 2).is_err());
+    }
+
+    #[test]
+    fn runs_group_contiguous_addresses() {
+        let runs = runs([1, 2, 3, 5, 65_534, 65_535], 2);
+        assert_eq!(runs, vec![(1, 2), (3, 1), (5, 1), (65_534, 2)]);
+    }
+}
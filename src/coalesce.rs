@@ -0,0 +1,343 @@
+use crate::{
+    chunk::PduLimits,
+    context::RobustContext,
+    types::{Coil, Word},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::pin::Pin;
+use tokio_modbus::{prelude::*, Address, Quantity, Result as ModbusResult};
+use tracing::warn;
+
+/// The address space a read goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegisterTable {
+    Coils,
+    DiscreteInputs,
+    Holding,
+    Input,
+}
+
+/// A single read request produced by [`ReadCoalescer::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadBlock {
+    pub addr: Address,
+    pub cnt: Quantity,
+}
+
+impl ReadBlock {
+    fn end(&self) -> u32 {
+        u32::from(self.addr) + u32::from(self.cnt)
+    }
+
+    pub(crate) fn contains(&self, addr: Address) -> bool {
+        addr >= self.addr && u32::from(addr) < self.end()
+    }
+}
+
+/// What was learned about the addresses of one table.
+#[derive(Debug, Clone, Default)]
+struct Holes {
+    forbidden: BTreeSet<Address>,
+    /// A block may not contain both `addr - 1` and `addr` for any `addr` in here.
+    breaks: BTreeSet<Address>,
+}
+
+/// Merges scattered addresses into as few reads as possible.
+///
+/// Gaps of up to `max_gap` addresses are read through unless they contain a forbidden
+/// address. Blocks rejected with `IllegalDataAddress` are split on the next plan. What
+/// is learned is kept per [`RegisterTable`], so one coalescer can serve all tables of a
+/// device.
+#[derive(Debug, Clone)]
+pub struct ReadCoalescer {
+    pub max_gap: Quantity,
+    pub max_cnt: Quantity,
+    holes: BTreeMap<RegisterTable, Holes>,
+}
+
+impl Default for ReadCoalescer {
+    fn default() -> Self {
+        Self {
+            max_gap: 8,
+            max_cnt: PduLimits::default().read_registers,
+            holes: BTreeMap::new(),
+        }
+    }
+}
+
+impl ReadCoalescer {
+    pub fn new(max_gap: Quantity, max_cnt: Quantity) -> Self {
+        Self {
+            max_gap,
+            max_cnt: max_cnt.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Marks addresses of `table` that must never be read through.
+    pub fn forbid(&mut self, table: RegisterTable, addrs: RangeInclusive<Address>) {
+        self.holes.entry(table).or_default().forbidden.extend(addrs);
+    }
+
+    pub fn plan(&self, table: RegisterTable, addrs: &[Address]) -> Vec<ReadBlock> {
+        let empty = Holes::default();
+        let holes = self.holes.get(&table).unwrap_or(&empty);
+        let addrs: BTreeSet<Address> = addrs.iter().copied().collect();
+        let mut blocks = Vec::new();
+        let mut iter = addrs.into_iter();
+        let Some(first) = iter.next() else {
+            return blocks;
+        };
+
+        let (mut start, mut last) = (first, first);
+        for addr in iter {
+            let gap = addr - last - 1;
+            let len = u32::from(addr - start) + 1;
+            if gap > self.max_gap
+                || len > u32::from(self.max_cnt)
+                || holes.forbidden.range(last + 1..addr).next().is_some()
+                || holes.breaks.range(last + 1..=addr).next().is_some()
+            {
+                blocks.push(ReadBlock {
+                    addr: start,
+                    cnt: last - start + 1,
+                });
+                start = addr;
+            }
+            last = addr;
+        }
+        blocks.push(ReadBlock {
+            addr: start,
+            cnt: last - start + 1,
+        });
+
+        blocks
+    }
+
+    /// Learns from a block rejected with `IllegalDataAddress`.
+    ///
+    /// Holes the block read through become forbidden. A block without holes is halved,
+    /// so repeated failures narrow down to the offending address.
+    pub fn report_illegal_data_address(
+        &mut self,
+        table: RegisterTable,
+        block: ReadBlock,
+        addrs: &[Address],
+    ) {
+        let wanted: BTreeSet<Address> = addrs
+            .iter()
+            .copied()
+            .filter(|addr| block.contains(*addr))
+            .collect();
+        let holes: Vec<Address> = (block.addr..=block.addr + (block.cnt - 1))
+            .filter(|addr| !wanted.contains(addr))
+            .collect();
+
+        let learned = self.holes.entry(table).or_default();
+        if !holes.is_empty() {
+            learned.forbidden.extend(holes);
+        } else if block.cnt > 1 {
+            learned.breaks.insert(block.addr + block.cnt / 2);
+        }
+    }
+}
+
+/// One of the [`Reader`] methods of [`RobustContext`].
+type ReadFn<T> = for<'a> fn(
+    &'a mut RobustContext,
+    ReadBlock,
+) -> Pin<Box<dyn Future<Output = ModbusResult<Vec<T>>> + Send + 'a>>;
+
+impl RobustContext {
+    /// Reads scattered coils with as few requests as `coalescer` allows.
+    ///
+    /// See [`RobustContext::read_holding_registers_coalesced`].
+    pub async fn read_coils_coalesced(
+        &mut self,
+        coalescer: &mut ReadCoalescer,
+        addrs: &[Address],
+    ) -> ModbusResult<BTreeMap<Address, Coil>> {
+        let read: ReadFn<Coil> = |ctx, block| ctx.read_coils(block.addr, block.cnt);
+        self.read_coalesced(RegisterTable::Coils, coalescer, addrs, read)
+            .await
+    }
+
+    /// Reads scattered discrete inputs with as few requests as `coalescer` allows.
+    ///
+    /// See [`RobustContext::read_holding_registers_coalesced`].
+    pub async fn read_discrete_inputs_coalesced(
+        &mut self,
+        coalescer: &mut ReadCoalescer,
+        addrs: &[Address],
+    ) -> ModbusResult<BTreeMap<Address, Coil>> {
+        let read: ReadFn<Coil> = |ctx, block| ctx.read_discrete_inputs(block.addr, block.cnt);
+        self.read_coalesced(RegisterTable::DiscreteInputs, coalescer, addrs, read)
+            .await
+    }
+
+    /// Reads scattered holding registers with as few requests as `coalescer` allows.
+    ///
+    /// Addresses of blocks the device rejects with `IllegalDataAddress` are missing from
+    /// the result and the coalescer splits those blocks on the next call.
+    pub async fn read_holding_registers_coalesced(
+        &mut self,
+        coalescer: &mut ReadCoalescer,
+        addrs: &[Address],
+    ) -> ModbusResult<BTreeMap<Address, Word>> {
+        let read: ReadFn<Word> = |ctx, block| ctx.read_holding_registers(block.addr, block.cnt);
+        self.read_coalesced(RegisterTable::Holding, coalescer, addrs, read)
+            .await
+    }
+
+    /// Reads scattered input registers with as few requests as `coalescer` allows.
+    ///
+    /// See [`RobustContext::read_holding_registers_coalesced`].
+    pub async fn read_input_registers_coalesced(
+        &mut self,
+        coalescer: &mut ReadCoalescer,
+        addrs: &[Address],
+    ) -> ModbusResult<BTreeMap<Address, Word>> {
+        let read: ReadFn<Word> = |ctx, block| ctx.read_input_registers(block.addr, block.cnt);
+        self.read_coalesced(RegisterTable::Input, coalescer, addrs, read)
+            .await
+    }
+
+    async fn read_coalesced<T: Copy>(
+        &mut self,
+        table: RegisterTable,
+        coalescer: &mut ReadCoalescer,
+        addrs: &[Address],
+        read: ReadFn<T>,
+    ) -> ModbusResult<BTreeMap<Address, T>> {
+        let mut values = BTreeMap::new();
+        for block in coalescer.plan(table, addrs) {
+            match read(self, block).await? {
+                Ok(items) => values.extend(
+                    addrs
+                        .iter()
+                        .filter(|addr| block.contains(**addr))
+                        .filter_map(|addr| {
+                            let item = items.get(usize::from(addr - block.addr))?;
+                            Some((*addr, *item))
+                        }),
+                ),
+                Err(ExceptionCode::IllegalDataAddress) => {
+                    warn!(
+                        "illegal data address in {:?} of {:?}, splitting block",
+                        block, table
+                    );
+                    coalescer.report_illegal_data_address(table, block, addrs);
+                }
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+
+        Ok(Ok(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+
+    fn block(addr: Address, cnt: Quantity) -> ReadBlock {
+        ReadBlock { addr, cnt }
+    }
+
+    #[test]
+    fn plan_reads_through_small_gaps() {
+        let coalescer = ReadCoalescer::new(2, 125);
+        let plan = coalescer.plan(RegisterTable::Holding, &[10, 12, 15, 19, 10]);
+        assert_eq!(plan, vec![block(10, 6), block(19, 1)]);
+    }
+
+    #[test]
+    fn plan_splits_at_max_cnt() {
+        let coalescer = ReadCoalescer::new(8, 4);
+        assert_eq!(
+            coalescer.plan(RegisterTable::Holding, &[0, 1, 2, 3, 4]),
+            vec![block(0, 4), block(4, 1)]
+        );
+    }
+
+    #[test]
+    fn plan_avoids_forbidden_addresses() {
+        let mut coalescer = ReadCoalescer::new(8, 125);
+        coalescer.forbid(RegisterTable::Holding, 3..=3);
+        assert_eq!(
+            coalescer.plan(RegisterTable::Holding, &[1, 5]),
+            vec![block(1, 1), block(5, 1)]
+        );
+    }
+
+    #[test]
+    fn plan_reaches_last_address() {
+        let coalescer = ReadCoalescer::new(8, 125);
+        let plan = coalescer.plan(RegisterTable::Holding, &[65_530, Address::MAX]);
+        assert_eq!(plan, vec![block(65_530, 6)]);
+    }
+
+    #[test]
+    fn illegal_address_forbids_holes() {
+        let mut coalescer = ReadCoalescer::new(8, 125);
+        let addrs = [1, 4];
+        coalescer.report_illegal_data_address(RegisterTable::Holding, block(1, 4), &addrs);
+        assert_eq!(
+            coalescer.plan(RegisterTable::Holding, &addrs),
+            vec![block(1, 1), block(4, 1)]
+        );
+    }
+
+    #[test]
+    fn illegal_address_halves_dense_block() {
+        let mut coalescer = ReadCoalescer::new(8, 125);
+        let addrs = [65_532, 65_533, 65_534, Address::MAX];
+        coalescer.report_illegal_data_address(RegisterTable::Holding, block(65_532, 4), &addrs);
+        assert_eq!(
+            coalescer.plan(RegisterTable::Holding, &addrs),
+            vec![block(65_532, 2), block(65_534, 2)]
+        );
+    }
+
+    #[test]
+    fn learned_holes_are_kept_per_table() {
+        let mut coalescer = ReadCoalescer::new(8, 125);
+        let addrs = [1, 4];
+        coalescer.report_illegal_data_address(RegisterTable::Input, block(1, 4), &addrs);
+        assert_eq!(
+            coalescer.plan(RegisterTable::Input, &addrs),
+            vec![block(1, 1), block(4, 1)]
+        );
+        assert_eq!(
+            coalescer.plan(RegisterTable::Holding, &addrs),
+            vec![block(1, 4)]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_splits_rejected_block_on_next_call() {
+        let mut image = RegisterImage::new();
+        image.set(0, &[1, 2, 3]);
+        image.set(5, &[6]);
+        let mut ctx = context(image).await;
+        let mut coalescer = ReadCoalescer::new(8, 125);
+        let addrs = [0, 5];
+
+        let values = ctx
+            .read_holding_registers_coalesced(&mut coalescer, &addrs)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(values.is_empty());
+
+        let values = ctx
+            .read_holding_registers_coalesced(&mut coalescer, &addrs)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(values, BTreeMap::from([(0, 1), (5, 6)]));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::{context::RobustContext, types::Word};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_modbus::{prelude::*, Address, Error as ModbusError, Quantity, Result as ModbusResult};
+
+/// Order of the two characters packed into a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    /// First character in the high byte.
+    #[default]
+    BigEndian,
+    /// First character in the low byte.
+    LittleEndian,
+}
+
+impl ByteOrder {
+    fn split(self, word: Word) -> [u8; 2] {
+        match self {
+            ByteOrder::BigEndian => word.to_be_bytes(),
+            ByteOrder::LittleEndian => word.to_le_bytes(),
+        }
+    }
+
+    fn join(self, bytes: [u8; 2]) -> Word {
+        match self {
+            ByteOrder::BigEndian => Word::from_be_bytes(bytes),
+            ByteOrder::LittleEndian => Word::from_le_bytes(bytes),
+        }
+    }
+}
+
+/// Filler for the unused tail of a string field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    #[default]
+    Nul,
+    Space,
+}
+
+/// Decodes an ASCII string, stopping at the first NUL and dropping trailing spaces.
+pub fn decode_string(words: &[Word], byte_order: ByteOrder) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|w| byte_order.split(*w))
+        .take_while(|b| *b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
+
+/// Encodes an ASCII string into exactly `cnt` registers.
+pub fn encode_string(
+    s: &str,
+    cnt: Quantity,
+    byte_order: ByteOrder,
+    padding: Padding,
+) -> io::Result<Vec<Word>> {
+    let len = usize::from(cnt) * 2;
+    if !s.is_ascii() || s.len() > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{s:?} does not fit into {cnt} registers as ASCII"),
+        ));
+    }
+
+    let fill = match padding {
+        Padding::Nul => 0,
+        Padding::Space => b' ',
+    };
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.resize(len, fill);
+
+    Ok(bytes
+        .chunks(2)
+        .map(|pair| byte_order.join([pair[0], pair[1]]))
+        .collect())
+}
+
+/// Decodes packed BCD digits, most significant register first.
+pub fn decode_bcd(words: &[Word]) -> io::Result<u64> {
+    words
+        .iter()
+        .flat_map(|w| [w >> 12, (w >> 8) & 0xf, (w >> 4) & 0xf, w & 0xf])
+        .try_fold(0u64, |acc, digit| match digit {
+            0..=9 => acc
+                .checked_mul(10)
+                .and_then(|acc| acc.checked_add(digit.into()))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "BCD value overflows")),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid BCD digit {digit:#x}"),
+            )),
+        })
+}
+
+/// Encodes `value` as packed BCD into exactly `cnt` registers.
+pub fn encode_bcd(mut value: u64, cnt: Quantity) -> io::Result<Vec<Word>> {
+    let mut words = vec![0; cnt.into()];
+    for word in words.iter_mut().rev() {
+        for shift in [0, 4, 8, 12] {
+            *word |= ((value % 10) as Word) << shift;
+            value /= 10;
+        }
+    }
+    if value != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("value does not fit into {cnt} BCD registers"),
+        ));
+    }
+
+    Ok(words)
+}
+
+/// A calendar date and time without time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millisecond: u16,
+}
+
+impl DateTime {
+    fn validate(self) -> io::Result<Self> {
+        let valid = (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+            && self.millisecond < 1000;
+        match valid {
+            true => Ok(self),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid date time {self:?}"),
+            )),
+        }
+    }
+
+    pub fn from_unix_timestamp(secs: u64) -> Self {
+        let days = (secs / 86400) as i64;
+        let rem = secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year: year as u16,
+            month,
+            day,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+            millisecond: 0,
+        }
+    }
+
+    /// Seconds since 1970-01-01 00:00, ignoring milliseconds.
+    pub fn unix_timestamp(&self) -> io::Result<u64> {
+        if self.year < 1970 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "date time before UNIX epoch",
+            ));
+        }
+        let days = days_from_civil(self.year.into(), self.month, self.day) as u64;
+        Ok(days * 86400
+            + u64::from(self.hour) * 3600
+            + u64::from(self.minute) * 60
+            + u64::from(self.second))
+    }
+
+    pub fn from_system_time(time: SystemTime) -> io::Result<Self> {
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            millisecond: since_epoch.subsec_millis() as u16,
+            ..Self::from_unix_timestamp(since_epoch.as_secs())
+        })
+    }
+
+    pub fn to_system_time(&self) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH
+            + Duration::from_secs(self.unix_timestamp()?)
+            + Duration::from_millis(self.millisecond.into()))
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since 1970-01-01 and the proleptic Gregorian calendar, following
+// Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Register layouts used for timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateTimeLayout {
+    /// Six registers: year, month, day, hour, minute, second.
+    Words,
+    /// Seconds since 1970-01-01 00:00 as u32, high word first.
+    UnixEpoch,
+    /// IEC 60870-5 CP56Time2a, seven bytes packed high byte first into four registers.
+    Cp56Time2a,
+}
+
+impl DateTimeLayout {
+    pub fn word_count(self) -> Quantity {
+        match self {
+            DateTimeLayout::Words => 6,
+            DateTimeLayout::UnixEpoch => 2,
+            DateTimeLayout::Cp56Time2a => 4,
+        }
+    }
+
+    pub fn decode(self, words: &[Word]) -> io::Result<DateTime> {
+        if words.len() < self.word_count().into() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not enough registers for date time",
+            ));
+        }
+
+        match self {
+            DateTimeLayout::Words => {
+                let field = |word: Word| {
+                    u8::try_from(word).map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid date time {words:?}"),
+                        )
+                    })
+                };
+                DateTime {
+                    year: words[0],
+                    month: field(words[1])?,
+                    day: field(words[2])?,
+                    hour: field(words[3])?,
+                    minute: field(words[4])?,
+                    second: field(words[5])?,
+                    millisecond: 0,
+                }
+                .validate()
+            }
+            DateTimeLayout::UnixEpoch => Ok(DateTime::from_unix_timestamp(
+                (u64::from(words[0]) << 16) | u64::from(words[1]),
+            )),
+            DateTimeLayout::Cp56Time2a => {
+                let b: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+                let ms = u16::from_le_bytes([b[0], b[1]]);
+                DateTime {
+                    year: 2000 + u16::from(b[6] & 0x7f),
+                    month: b[5] & 0x0f,
+                    day: b[4] & 0x1f,
+                    hour: b[3] & 0x1f,
+                    minute: b[2] & 0x3f,
+                    second: (ms / 1000) as u8,
+                    millisecond: ms % 1000,
+                }
+                .validate()
+            }
+        }
+    }
+
+    pub fn encode(self, date_time: &DateTime) -> io::Result<Vec<Word>> {
+        let dt = date_time.validate()?;
+        match self {
+            DateTimeLayout::Words => Ok(vec![
+                dt.year,
+                dt.month.into(),
+                dt.day.into(),
+                dt.hour.into(),
+                dt.minute.into(),
+                dt.second.into(),
+            ]),
+            DateTimeLayout::UnixEpoch => {
+                let secs = u32::try_from(dt.unix_timestamp()?).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "date time beyond u32 epoch")
+                })?;
+                Ok(vec![(secs >> 16) as Word, secs as Word])
+            }
+            DateTimeLayout::Cp56Time2a => {
+                if !(2000..2100).contains(&dt.year) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "CP56Time2a only covers the years 2000 to 2099",
+                    ));
+                }
+                let [ms_lo, ms_hi] = (u16::from(dt.second) * 1000 + dt.millisecond).to_le_bytes();
+                let day_of_week =
+                    (days_from_civil(dt.year.into(), dt.month, dt.day) + 3).rem_euclid(7) as u8 + 1;
+                let b = [
+                    ms_lo,
+                    ms_hi,
+                    dt.minute,
+                    dt.hour,
+                    (day_of_week << 5) | dt.day,
+                    dt.month,
+                    (dt.year - 2000) as u8,
+                    0,
+                ];
+                Ok(b.chunks(2)
+                    .map(|pair| Word::from_be_bytes([pair[0], pair[1]]))
+                    .collect())
+            }
+        }
+    }
+}
+
+impl RobustContext {
+    pub async fn read_string(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+        byte_order: ByteOrder,
+    ) -> ModbusResult<String> {
+        self.read_holding_registers(addr, cnt)
+            .await
+            .map(|res| res.map(|words| decode_string(&words, byte_order)))
+    }
+
+    pub async fn write_string(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+        s: &str,
+        byte_order: ByteOrder,
+        padding: Padding,
+    ) -> ModbusResult<()> {
+        let words = encode_string(s, cnt, byte_order, padding).map_err(ModbusError::Transport)?;
+        self.write_multiple_registers(addr, &words).await
+    }
+
+    pub async fn read_bcd(&mut self, addr: Address, cnt: Quantity) -> ModbusResult<u64> {
+        let words = match self.read_holding_registers(addr, cnt).await? {
+            Ok(words) => words,
+            Err(e) => return Ok(Err(e)),
+        };
+        decode_bcd(&words).map(Ok).map_err(ModbusError::Transport)
+    }
+
+    pub async fn write_bcd(
+        &mut self,
+        addr: Address,
+        cnt: Quantity,
+        value: u64,
+    ) -> ModbusResult<()> {
+        let words = encode_bcd(value, cnt).map_err(ModbusError::Transport)?;
+        self.write_multiple_registers(addr, &words).await
+    }
+
+    pub async fn read_date_time(
+        &mut self,
+        addr: Address,
+        layout: DateTimeLayout,
+    ) -> ModbusResult<DateTime> {
+        let words = match self
+            .read_holding_registers(addr, layout.word_count())
+            .await?
+        {
+            Ok(words) => words,
+            Err(e) => return Ok(Err(e)),
+        };
+        layout
+            .decode(&words)
+            .map(Ok)
+            .map_err(ModbusError::Transport)
+    }
+
+    pub async fn write_date_time(
+        &mut self,
+        addr: Address,
+        layout: DateTimeLayout,
+        date_time: &DateTime,
+    ) -> ModbusResult<()> {
+        let words = layout.encode(date_time).map_err(ModbusError::Transport)?;
+        self.write_multiple_registers(addr, &words).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_round_trip() {
+        for byte_order in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+            let words = encode_string("abc", 3, byte_order, Padding::Space).unwrap();
+            assert_eq!(words.len(), 3);
+            assert_eq!(decode_string(&words, byte_order), "abc");
+        }
+        assert!(encode_string("abcde", 2, ByteOrder::BigEndian, Padding::Nul).is_err());
+    }
+
+    #[test]
+    fn bcd_round_trip() {
+        let words = encode_bcd(12_345_678, 2).unwrap();
+        assert_eq!(words, vec![0x1234, 0x5678]);
+        assert_eq!(decode_bcd(&words).unwrap(), 12_345_678);
+        assert!(encode_bcd(123_456_789, 2).is_err());
+    }
+
+    #[test]
+    fn bcd_rejects_invalid_digits() {
+        assert!(decode_bcd(&[0x12a4]).is_err());
+    }
+
+    #[test]
+    fn bcd_rejects_overflow() {
+        assert_eq!(
+            decode_bcd(&[0x1844, 0x6744, 0x0737, 0x0955, 0x1615]).unwrap(),
+            u64::MAX
+        );
+        assert!(decode_bcd(&[0x1844, 0x6744, 0x0737, 0x0955, 0x1616]).is_err());
+        assert!(decode_bcd(&[0x9999; 5]).is_err());
+    }
+
+    fn date_time() -> DateTime {
+        DateTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 58,
+            millisecond: 0,
+        }
+    }
+
+    #[test]
+    fn date_time_round_trip() {
+        for layout in [
+            DateTimeLayout::Words,
+            DateTimeLayout::UnixEpoch,
+            DateTimeLayout::Cp56Time2a,
+        ] {
+            let words = layout.encode(&date_time()).unwrap();
+            assert_eq!(words.len(), usize::from(layout.word_count()));
+            assert_eq!(layout.decode(&words).unwrap(), date_time(), "{layout:?}");
+        }
+    }
+
+    #[test]
+    fn date_time_unix_timestamp() {
+        assert_eq!(date_time().unix_timestamp().unwrap(), 1_709_251_198);
+        assert_eq!(DateTime::from_unix_timestamp(1_709_251_198), date_time());
+    }
+
+    #[test]
+    fn date_time_rejects_invalid_fields() {
+        let words = [2023, 2, 29, 0, 0, 0];
+        assert!(DateTimeLayout::Words.decode(&words).is_err());
+        // Month 257 must not truncate to January.
+        let words = [2024, 257, 1, 0, 0, 0];
+        assert!(DateTimeLayout::Words.decode(&words).is_err());
+        assert!(DateTimeLayout::Words.decode(&[2024, 1]).is_err());
+    }
+}
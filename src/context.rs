@@ -1,19 +1,66 @@
+use crate::{
+    breaker::Breaker,
+    cache::RegisterCache,
+    capture::{self, Capture, CaptureStream},
+    chunk::PduLimits,
+    desired::DesiredState,
+    error::{RobustError, TaggedStream},
+    heartbeat::HeartbeatGuard,
+    hooks::{DisconnectCause, Hooks},
+    in_flight::InFlight,
+    liveness::TcpOptions,
+    metrics,
+    outage::OutageLog,
+    priority::{Priority, PriorityGate},
+    rate_limit::{RateLimit, RateLimiter},
+    single_flight::{self, Flight, ReadKey, SingleFlight},
+    stats::StatsCollector,
+    write_queue::WriteQueue,
+};
+use std::future::Future;
 use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
 use tokio::sync::{mpsc, Mutex};
-use tokio_modbus::{prelude::*, Result as ModbusResult};
+use tokio_modbus::{
+    prelude::*, Address, Error as ModbusError, FunctionCode, Result as ModbusResult,
+};
 use tokio_retry::strategy::{jitter, FixedInterval};
-use tokio_retry::Retry;
-use tracing::{error, info};
+use tokio_retry::{Retry, RetryIf};
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
 
-#[derive(Debug)]
+/// Clones share the connection, see [`RobustContext::with_priority`].
+#[derive(Debug, Clone)]
 pub struct RobustContext {
     pub host: String,
     pub slave: Slave,
+    pub limits: PduLimits,
+    pub priority: Priority,
+    /// How often a background request may probe the link while it is degraded.
+    pub background_probe_interval: Duration,
     slave_sender: mpsc::Sender<Slave>,
     pub ctx: Arc<Mutex<io::Result<client::Context>>>,
+    pub(crate) connected: Arc<AtomicBool>,
+    /// Set when the connection was closed after a cancelled request.
+    pub(crate) abandoned: Arc<AtomicBool>,
+    pub(crate) cache: Arc<OnceLock<Arc<RegisterCache>>>,
+    pub(crate) write_queue: Arc<OnceLock<Arc<WriteQueue>>>,
+    pub(crate) desired_state: Arc<OnceLock<Arc<DesiredState>>>,
+    pub(crate) heartbeat: Arc<std::sync::Mutex<Option<HeartbeatGuard>>>,
+    pub(crate) hooks: Arc<Hooks>,
+    gate: Arc<PriorityGate>,
+    flights: Arc<SingleFlight>,
+    rate_limiter: Arc<RateLimiter>,
+    last_probe: Arc<std::sync::Mutex<Option<Instant>>>,
+    last_response: Arc<std::sync::Mutex<Instant>>,
+    pub(crate) capture: Arc<std::sync::Mutex<Option<Arc<Capture>>>>,
+    pub(crate) tcp_options: Arc<std::sync::Mutex<TcpOptions>>,
+    pub(crate) breaker: Arc<Breaker>,
+    pub(crate) stats: Arc<StatsCollector>,
+    pub(crate) outage_log: Arc<OutageLog>,
 }
 
 impl RobustContext {
@@ -37,8 +84,28 @@ impl RobustContext {
         Ok(Self {
             host: host.to_string(),
             slave,
+            limits: PduLimits::default(),
+            priority: Priority::default(),
+            background_probe_interval: Duration::from_secs(5),
             slave_sender,
             ctx,
+            connected: Arc::new(AtomicBool::new(false)),
+            abandoned: Arc::default(),
+            cache: Arc::default(),
+            write_queue: Arc::default(),
+            desired_state: Arc::default(),
+            heartbeat: Arc::default(),
+            hooks: Arc::default(),
+            gate: Arc::default(),
+            flights: Arc::default(),
+            rate_limiter: Arc::default(),
+            last_probe: Arc::default(),
+            last_response: Arc::new(std::sync::Mutex::new(Instant::now())),
+            capture: Arc::default(),
+            tcp_options: Arc::default(),
+            breaker: Arc::default(),
+            stats: Arc::default(),
+            outage_log: Arc::default(),
         })
     }
 
@@ -57,23 +124,122 @@ impl RobustContext {
         FixedInterval::from_millis(10).map(jitter).take(3)
     }
 
-    async fn set_context(
-        ctx: Arc<Mutex<io::Result<client::Context>>>,
-        host: &str,
-        slave: Slave,
-    ) -> io::Result<()> {
-        let socket_addr = RobustContext::resolve_host(host)?;
+    /// [`RobustContext::retry_strategy_command`], counting every retry and recording the
+    /// attempt number on the current request span.
+    pub(crate) fn command_retries(&self) -> impl Iterator<Item = Duration> + '_ {
+        let span = Span::current();
+        span.record("attempt", 1);
+        RobustContext::retry_strategy_command()
+            .zip(2_u32..)
+            .map(move |(delay, attempt)| {
+                span.record("attempt", attempt);
+                metrics::record_retry(&self.host, self.slave);
+                delay
+            })
+    }
 
-        let mut ctx_guard = ctx.lock().await;
-        info!("trying to connect modbus: {:?}", ctx_guard);
-        *ctx_guard = tcp::connect_slave(socket_addr, slave).await;
+    /// Runs `action` until it succeeds or the command retries are used up, in which case
+    /// the last error is wrapped into [`RobustError::RetriesExhausted`].
+    pub(crate) async fn retry<T, A, F>(&self, action: A) -> ModbusResult<T>
+    where
+        A: FnMut() -> F,
+        F: Future<Output = ModbusResult<T>>,
+    {
+        let attempts = AtomicU32::new(1);
+        let retries = self.command_retries().inspect(|_| {
+            attempts.fetch_add(1, Ordering::Relaxed);
+        });
+        // Requests rejected without being sent are not retried.
+        let retryable = |e: &ModbusError| {
+            !matches!(
+                RobustError::downcast(e),
+                Some(RobustError::CircuitOpen | RobustError::Cancelled)
+            )
+        };
+        RetryIf::spawn(retries, action, retryable)
+            .await
+            .map_err(|last| match RobustError::from(last) {
+                last @ (RobustError::CircuitOpen | RobustError::Cancelled) => last.into(),
+                last => RobustError::RetriesExhausted {
+                    attempts: attempts.load(Ordering::Relaxed),
+                    last: Box::new(last),
+                }
+                .into(),
+            })
+    }
+
+    /// Span of a [`Reader`] or [`Writer`] call.
+    pub(crate) fn request_span(&self, function: FunctionCode, addr: Address, cnt: usize) -> Span {
+        info_span!(
+            "modbus_request",
+            host = %self.host,
+            unit = self.slave.0,
+            function = %format_args!("{:#04x}", function.value()),
+            addr,
+            cnt,
+            attempt = field::Empty,
+        )
+    }
+
+    async fn set_context(&self) -> io::Result<()> {
+        let span = info_span!(
+            "modbus_connect",
+            host = %self.host,
+            unit = self.slave.0,
+            addr = field::Empty,
+            error = field::Empty,
+        );
+        let res = self.connect().instrument(span.clone()).await;
+        if let Err(e) = &res {
+            span.record("error", field::debug(e.kind()));
+        }
+        res
+    }
+
+    async fn connect(&self) -> io::Result<()> {
+        let socket_addr = RobustContext::resolve_host(&self.host)?;
+        Span::current().record("addr", field::display(socket_addr));
+
+        let mut ctx_guard = self.ctx.lock().await;
+        debug!("trying to connect modbus: {}", socket_addr);
+        let res = match self.connect_tcp(socket_addr).await {
+            Ok(mut ctx) => self.hooks.connected(&mut ctx).await.map(|()| ctx),
+            Err(e) => Err(e),
+        };
+        metrics::record_connect(&self.host, self.slave, res.is_ok());
+        self.stats.record_connect(res.as_ref().map(|_| ()));
 
+        // The error is kept in place of the connection, to be shared with every request
+        // that finds no connection.
+        *ctx_guard = res.map_err(|e| RobustError::ConnectFailed(Arc::new(e)).into());
         match ctx_guard.as_ref() {
-            Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            Err(e) => Err(RobustError::connect_failed(e).into()),
             Ok(_) => Ok(()),
         }
     }
 
+    async fn connect_tcp(&self, socket_addr: SocketAddr) -> io::Result<client::Context> {
+        let capture = self.capture();
+        let stream = match TcpStream::connect(socket_addr).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                if let Some(capture) = capture {
+                    capture::record_failed_connect(capture, socket_addr);
+                }
+                return Err(e);
+            }
+        };
+        self.tcp_options().apply(&stream)?;
+
+        match capture {
+            Some(capture) => {
+                let stream = CaptureStream::new(stream, capture)?;
+                Ok(tcp::attach_slave(TaggedStream(stream), self.slave))
+            }
+            None => Ok(tcp::attach_slave(TaggedStream(stream), self.slave)),
+        }
+    }
+
     async fn set_slave(
         ctx: Arc<Mutex<io::Result<client::Context>>>,
         slave: Slave,
@@ -87,17 +253,166 @@ impl RobustContext {
         Ok(())
     }
 
+    /// Reconnects `ctx` to `host`.
+    ///
+    /// Only replaces the connection: hooks, TCP options, capture, statistics and the
+    /// connection state of the context owning `ctx` are left alone.
+    #[deprecated(note = "use `RobustContext::reconnect`, which reconnects all clones")]
     pub async fn refresh_context(
         ctx: Arc<Mutex<io::Result<client::Context>>>,
         host: &str,
         slave: Slave,
     ) {
-        let action = || RobustContext::set_context(ctx.clone(), host, slave);
+        let action = || async {
+            let socket_addr = RobustContext::resolve_host(host)?;
+            let mut ctx_guard = ctx.lock().await;
+            *ctx_guard = tcp::connect_slave(socket_addr, slave).await;
+            match ctx_guard.as_ref() {
+                Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+                Ok(_) => Ok(()),
+            }
+        };
         match Retry::spawn(RobustContext::retry_strategy_connect(), action).await {
             Ok(_) => info!("successfully reconnected modbus"),
             Err(_) => error!("could not reconnect modbus"),
         };
     }
+
+    /// Reconnects this context and all its clones, then replays queued writes and
+    /// reconciles setpoints.
+    pub async fn reconnect(&self) {
+        let attempts = AtomicU64::new(0);
+        let action = || {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            self.set_context()
+        };
+        let res = Retry::spawn(RobustContext::retry_strategy_connect(), action).await;
+        self.connected.store(res.is_ok(), Ordering::Relaxed);
+        let attempts = attempts.into_inner();
+        match res {
+            Ok(_) => {
+                self.outage_log.recovered(attempts - 1);
+                self.replay_writes().await;
+                if let Err(e) = self.reconcile().await {
+                    warn!("could not reconcile setpoints: {}", e);
+                }
+            }
+            Err(e) => self.outage_log.failed(&e, attempts),
+        };
+    }
+
+    /// Whether the last connection attempt succeeded and no request failed since.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// When the device last responded, with data or an exception.
+    pub fn last_response(&self) -> Instant {
+        *self.last_response.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A handle on the same connection whose requests are served with `priority`.
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        Self {
+            priority,
+            ..self.clone()
+        }
+    }
+
+    pub fn rate_limit(&self) -> RateLimit {
+        self.rate_limiter.limit()
+    }
+
+    /// Limits the request rate to the device, shared by all clones of this context.
+    ///
+    /// Fails if `limit` has a rate that is not positive and finite, or a burst of 0.
+    pub fn set_rate_limit(&self, limit: RateLimit) -> io::Result<()> {
+        self.rate_limiter.set_limit(limit)
+    }
+
+    /// Background requests are cancelled while the link is down, except for one probe
+    /// per `background_probe_interval` that gets to reconnect.
+    fn cancel_background(&self) -> bool {
+        if self.priority != Priority::Background || self.is_connected() {
+            return false;
+        }
+
+        let mut last_probe = self.last_probe.lock().unwrap_or_else(|e| e.into_inner());
+        match *last_probe {
+            Some(probe) if probe.elapsed() < self.background_probe_interval => true,
+            _ => {
+                *last_probe = Some(Instant::now());
+                false
+            }
+        }
+    }
+
+    /// Performs a single attempt of `request`, reconnecting if it fails.
+    ///
+    /// Identical reads in flight on any clone of this context share one response.
+    pub(crate) async fn try_call(&self, request: Request<'_>) -> ModbusResult<Response> {
+        if self.cancel_background() {
+            debug!("link degraded, background request cancelled");
+            return Err(RobustError::Cancelled.into());
+        }
+        let Some(admission) = self.breaker.admit() else {
+            return Err(RobustError::CircuitOpen.into());
+        };
+
+        let res = self.call_shared(request).await;
+        admission.record(res.is_ok());
+        res
+    }
+
+    async fn call_shared(&self, request: Request<'_>) -> ModbusResult<Response> {
+        let Some(key) = ReadKey::new(self.slave, &request) else {
+            return self.call_once(request).await;
+        };
+        match self.flights.join(key) {
+            Flight::Leader(flight) => flight.complete(self.call_once(request).await),
+            Flight::Follower(mut receiver) => match single_flight::wait(&mut receiver).await {
+                Some(res) => res,
+                None => self.call_once(request).await,
+            },
+        }
+    }
+
+    async fn call_once(&self, request: Request<'_>) -> ModbusResult<Response> {
+        self.reconnect_abandoned().await;
+        let res = self.send(request).await;
+        if let Err(e) = &res {
+            self.mark_disconnected(DisconnectCause::transport(e)).await;
+            self.reconnect().await;
+        }
+
+        res
+    }
+
+    /// Sends `request` in turn, without reconnecting.
+    pub(crate) async fn send(&self, request: Request<'_>) -> ModbusResult<Response> {
+        let _permit = self.gate.acquire(self.priority).await;
+        self.rate_limiter.acquire().await;
+        let function = request.function_code();
+        let mut in_flight = InFlight::new(self, self.ctx.lock().await);
+        let start = Instant::now();
+        let (res, sent) = match in_flight.ctx() {
+            Ok(ctx) => {
+                let res = ctx.call(request).await;
+                (res.map_err(|e| RobustError::from(e).into()), true)
+            }
+            Err(e) => (Err(RobustError::connect_failed(e).into()), false),
+        };
+        in_flight.complete();
+        let latency = start.elapsed();
+        metrics::record_request(&self.host, self.slave, function, &res, latency);
+        self.stats
+            .record_request(function, &res, sent.then_some(latency));
+        if res.is_ok() {
+            *self.last_response.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        }
+
+        res
+    }
 }
 
 impl SlaveContext for RobustContext {
@@ -108,7 +423,6 @@ impl SlaveContext for RobustContext {
 
 impl Client for RobustContext {
     #[doc = " Invoke a _Modbus_ function"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn call<'life0, 'life1, 'async_trait>(
         &'life0 mut self,
@@ -126,12 +440,18 @@ impl Client for RobustContext {
         Self: 'async_trait,
     {
         Box::pin(async {
-            let mut ctx_guard = self.ctx.lock().await;
-            let ctx = ctx_guard
+            self.reconnect_abandoned().await;
+            let _permit = self.gate.acquire(self.priority).await;
+            self.rate_limiter.acquire().await;
+            let mut in_flight = InFlight::new(self, self.ctx.lock().await);
+            let ctx = in_flight
+                .ctx()
                 .as_mut()
-                .map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+                .map_err(|e| RobustError::connect_failed(e))?;
 
-            ctx.call(request).await
+            let res = ctx.call(request).await;
+            in_flight.complete();
+            res
         })
     }
 
@@ -144,7 +464,6 @@ impl Client for RobustContext {
     #[doc = " beforehand should also work and free all resources. The"]
     #[doc = " actual behavior might depend on the underlying transport"]
     #[doc = " protocol (RTU/TCP) that is used by the client."]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn disconnect<'life0, 'async_trait>(
         &'life0 mut self,
@@ -160,12 +479,17 @@ impl Client for RobustContext {
         Self: 'async_trait,
     {
         Box::pin(async {
+            self.stop_heartbeat();
+
             let mut ctx_guard = self.ctx.lock().await;
             let ctx = ctx_guard
                 .as_mut()
-                .map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+                .map_err(|e| RobustError::connect_failed(e))?;
 
-            ctx.disconnect().await
+            let res = ctx.disconnect().await;
+            drop(ctx_guard);
+            self.mark_disconnected(DisconnectCause::Disconnected).await;
+            res
         })
     }
 }
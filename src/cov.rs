@@ -0,0 +1,295 @@
+use crate::{
+    point::Point,
+    poller::{PollEvent, PollRead, PollSample, PollValues, Poller, PollerHandle},
+};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep_until, Instant};
+use tokio_modbus::Address;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+
+/// A value inside the data of a poll group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchPoint {
+    Coil(Address),
+    DiscreteInput(Address),
+    HoldingRegister(Point),
+    InputRegister(Point),
+}
+
+impl WatchPoint {
+    fn extract(&self, read: &PollRead, values: &PollValues) -> Option<Value> {
+        let bit = |addr: Address, start: Address, coils: &[bool]| {
+            addr.checked_sub(start)
+                .and_then(|offset| coils.get(usize::from(offset)))
+                .copied()
+                .map(Value::Bool)
+        };
+
+        match (self, read, values) {
+            (WatchPoint::Coil(addr), PollRead::Coils(start, _), PollValues::Coils(coils))
+            | (
+                WatchPoint::DiscreteInput(addr),
+                PollRead::DiscreteInputs(start, _),
+                PollValues::Coils(coils),
+            ) => bit(*addr, *start, coils),
+            (
+                WatchPoint::HoldingRegister(point),
+                PollRead::HoldingRegisters(start, _),
+                PollValues::Words(words),
+            )
+            | (
+                WatchPoint::InputRegister(point),
+                PollRead::InputRegisters(start, _),
+                PollValues::Words(words),
+            ) => point.decode_from(*start, words).map(Value::Analog),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Analog(f64),
+}
+
+/// How much an analog value has to move before a change is reported.
+///
+/// Boolean values always report exact changes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Deadband {
+    #[default]
+    Exact,
+    Absolute(f64),
+    /// Percent of the last reported value.
+    Percent(f64),
+}
+
+impl Deadband {
+    fn exceeded(&self, last: Value, value: Value) -> bool {
+        match (last, value) {
+            (Value::Analog(last), Value::Analog(value)) => {
+                if last.is_nan() != value.is_nan() {
+                    return true;
+                }
+                let delta = (value - last).abs();
+                match self {
+                    Deadband::Exact => delta > 0.0,
+                    Deadband::Absolute(band) => delta > *band,
+                    Deadband::Percent(pct) => delta > last.abs() * pct / 100.0,
+                }
+            }
+            (last, value) => last != value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    pub point: WatchPoint,
+    pub value: Value,
+    /// Last reported value, `None` for the first report.
+    pub previous: Option<Value>,
+    pub timestamp: SystemTime,
+    /// Re-emitted because nothing was reported for the max silence interval.
+    pub heartbeat: bool,
+}
+
+struct Watcher {
+    point: WatchPoint,
+    deadband: Deadband,
+    max_silence: Option<Duration>,
+    current: Option<Value>,
+    reported: Option<Value>,
+    last_report: Instant,
+}
+
+impl Watcher {
+    fn sample(&mut self, sample: &PollSample) -> Option<Change> {
+        let values = sample.result.as_ref().ok()?;
+        let value = self.point.extract(&sample.read, values)?;
+        self.current = Some(value);
+
+        let changed = match self.reported {
+            Some(reported) => self.deadband.exceeded(reported, value),
+            None => true,
+        };
+        changed.then(|| self.report(value, sample.timestamp, false))
+    }
+
+    fn heartbeat(&mut self) -> Option<Change> {
+        let value = self.current?;
+        Some(self.report(value, SystemTime::now(), true))
+    }
+
+    fn report(&mut self, value: Value, timestamp: SystemTime, heartbeat: bool) -> Change {
+        let previous = self.reported.replace(value);
+        self.last_report = Instant::now();
+        Change {
+            point: self.point,
+            value,
+            previous,
+            timestamp,
+            heartbeat,
+        }
+    }
+
+    fn silence_deadline(&self) -> Option<Instant> {
+        self.max_silence
+            .filter(|_| self.current.is_some())
+            .map(|max_silence| self.last_report + max_silence)
+    }
+
+    async fn run(
+        mut self,
+        mut events: broadcast::Receiver<PollEvent>,
+        sender: mpsc::Sender<Change>,
+    ) {
+        loop {
+            let silence = self.silence_deadline();
+            let change = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(PollEvent::Sample(sample)) => self.sample(&sample),
+                    Ok(PollEvent::Overrun { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = sleep_until(silence.unwrap_or_else(Instant::now)), if silence.is_some() => {
+                    self.heartbeat()
+                }
+                _ = sender.closed() => break,
+            };
+
+            if let Some(change) = change {
+                if sender.send(change).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn subscribe_changes(
+    events: broadcast::Receiver<PollEvent>,
+    point: WatchPoint,
+    deadband: Deadband,
+    max_silence: Option<Duration>,
+) -> impl Stream<Item = Change> {
+    let (sender, receiver) = mpsc::channel(64);
+    let watcher = Watcher {
+        point,
+        deadband,
+        max_silence,
+        current: None,
+        reported: None,
+        last_report: Instant::now(),
+    };
+    tokio::spawn(watcher.run(events, sender));
+
+    ReceiverStream::new(receiver)
+}
+
+impl Poller {
+    /// Reports changes of `point` beyond `deadband`.
+    ///
+    /// With `max_silence` set, the current value is re-emitted as a heartbeat whenever
+    /// nothing was reported for that long.
+    pub fn subscribe_changes(
+        &self,
+        point: WatchPoint,
+        deadband: Deadband,
+        max_silence: Option<Duration>,
+    ) -> impl Stream<Item = Change> {
+        subscribe_changes(self.subscribe(), point, deadband, max_silence)
+    }
+}
+
+impl PollerHandle {
+    /// See [`Poller::subscribe_changes`].
+    pub fn subscribe_changes(
+        &self,
+        point: WatchPoint,
+        deadband: Deadband,
+        max_silence: Option<Duration>,
+    ) -> impl Stream<Item = Change> {
+        subscribe_changes(self.subscribe(), point, deadband, max_silence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+    use crate::point::DataType;
+    use crate::poller::PollGroup;
+    use tokio::time::timeout;
+    use tokio_modbus::prelude::*;
+    use tokio_stream::StreamExt;
+
+    fn analog(value: f64) -> Value {
+        Value::Analog(value)
+    }
+
+    #[test]
+    fn absolute_deadband() {
+        let band = Deadband::Absolute(0.5);
+        assert!(!band.exceeded(analog(10.0), analog(10.5)));
+        assert!(band.exceeded(analog(10.0), analog(9.4)));
+        assert!(Deadband::Exact.exceeded(analog(10.0), analog(10.01)));
+    }
+
+    #[test]
+    fn percent_deadband() {
+        let band = Deadband::Percent(10.0);
+        assert!(!band.exceeded(analog(-200.0), analog(-181.0)));
+        assert!(band.exceeded(analog(-200.0), analog(-179.0)));
+        // Any move away from zero exceeds a percentage of it.
+        assert!(band.exceeded(analog(0.0), analog(0.001)));
+    }
+
+    #[test]
+    fn nan_transitions_are_changes() {
+        let band = Deadband::Absolute(1000.0);
+        assert!(band.exceeded(analog(1.0), analog(f64::NAN)));
+        assert!(band.exceeded(analog(f64::NAN), analog(1.0)));
+        assert!(!band.exceeded(analog(f64::NAN), analog(f64::NAN)));
+        assert!(band.exceeded(Value::Bool(false), Value::Bool(true)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_changes_and_silence() {
+        let mut image = RegisterImage::new();
+        image.set(0, &[7]);
+        let mut ctx = context(image).await;
+        let group = PollGroup::new("fast", Duration::from_secs(1))
+            .with_read(PollRead::HoldingRegisters(0, 1));
+        let poller = Poller::new(ctx.clone()).with_group(group);
+        let point = WatchPoint::HoldingRegister(Point::new(0, DataType::U16));
+        let silence = Some(Duration::from_secs(5));
+        let changes = poller.subscribe_changes(point, Deadband::Absolute(2.0), silence);
+        let handle = poller.spawn();
+        tokio::pin!(changes);
+
+        let first = changes.next().await.unwrap();
+        assert_eq!((first.value, first.previous), (analog(7.0), None));
+        assert!(!first.heartbeat);
+
+        // Within the deadband, so only the heartbeat reports it.
+        ctx.write_single_register(0, 8).await.unwrap().unwrap();
+        let start = Instant::now();
+        let silent = changes.next().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(silent.value, analog(8.0));
+        assert_eq!(silent.previous, Some(analog(7.0)));
+        assert!(silent.heartbeat);
+
+        ctx.write_single_register(0, 11).await.unwrap().unwrap();
+        let change = timeout(Duration::from_millis(1100), changes.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(change.value, analog(11.0));
+        assert!(!change.heartbeat);
+        handle.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    chunk::runs,
+    context::RobustContext,
+    hooks::DisconnectCause,
+    poller::PollError,
+    types::{Coil, Word},
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_modbus::{prelude::*, Address, Error as ModbusError};
+use tracing::{info, warn};
+
+/// The value a coil or holding register should hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setpoint {
+    Coil(Address, Coil),
+    Register(Address, Word),
+}
+
+/// A setpoint that had drifted and was rewritten.
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub desired: Setpoint,
+    /// Value read back from the device.
+    pub found: Setpoint,
+    pub timestamp: SystemTime,
+    pub result: Result<(), PollError>,
+}
+
+#[derive(Debug, Default)]
+struct Setpoints {
+    coils: BTreeMap<Address, Coil>,
+    registers: BTreeMap<Address, Word>,
+}
+
+/// Values that are reasserted after every reconnect.
+#[derive(Debug)]
+pub struct DesiredState {
+    setpoints: Mutex<Setpoints>,
+    sender: broadcast::Sender<Correction>,
+}
+
+impl Default for DesiredState {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(64);
+        Self {
+            setpoints: Mutex::default(),
+            sender,
+        }
+    }
+}
+
+impl DesiredState {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Setpoints> {
+        self.setpoints.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, setpoint: Setpoint) {
+        let mut setpoints = self.lock();
+        match setpoint {
+            Setpoint::Coil(addr, coil) => {
+                setpoints.coils.insert(addr, coil);
+            }
+            Setpoint::Register(addr, word) => {
+                setpoints.registers.insert(addr, word);
+            }
+        }
+    }
+
+    pub fn remove_coil(&self, addr: Address) {
+        self.lock().coils.remove(&addr);
+    }
+
+    pub fn remove_register(&self, addr: Address) {
+        self.lock().registers.remove(&addr);
+    }
+
+    pub fn setpoints(&self) -> Vec<Setpoint> {
+        let setpoints = self.lock();
+        let coils = setpoints
+            .coils
+            .iter()
+            .map(|(addr, coil)| Setpoint::Coil(*addr, *coil));
+        let registers = setpoints
+            .registers
+            .iter()
+            .map(|(addr, word)| Setpoint::Register(*addr, *word));
+        coils.chain(registers).collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Correction> {
+        self.sender.subscribe()
+    }
+}
+
+impl RobustContext {
+    /// Starts reasserting declared setpoints after every reconnect. The registry is shared
+    /// by all clones of this context, including those made before it was enabled.
+    pub fn enable_desired_state(&self) -> Arc<DesiredState> {
+        self.desired_state.get_or_init(Arc::default).clone()
+    }
+
+    /// Reads back all setpoints and rewrites those that drifted. Returns the number of
+    /// corrections.
+    ///
+    /// Does not reconnect; a transport error aborts the pass.
+    pub async fn reconcile(&self) -> Result<usize, ModbusError> {
+        let Some(desired) = self.desired_state.get() else {
+            return Ok(0);
+        };
+        let (coils, registers) = {
+            let setpoints = desired.lock();
+            (setpoints.coils.clone(), setpoints.registers.clone())
+        };
+
+        let mut corrections = 0;
+        for (addr, cnt) in runs(coils.keys().copied(), self.limits.read_coils) {
+            let found = match self.reconcile_send(Request::ReadCoils(addr, cnt)).await? {
+                Ok(Response::ReadCoils(found)) => found,
+                Ok(_) => unreachable!("call() should reject mismatching responses"),
+                Err(e) => {
+                    warn!("could not read back coils {}+{}: {}", addr, cnt, e);
+                    continue;
+                }
+            };
+            let addrs = (0..cnt).filter_map(|offset| addr.checked_add(offset));
+            for (addr, found) in addrs.zip(found) {
+                let Some(&desired) = coils.get(&addr) else {
+                    continue;
+                };
+                if found != desired {
+                    let request = Request::WriteSingleCoil(addr, desired);
+                    let result = self.reconcile_send(request).await?;
+                    self.correct(
+                        Setpoint::Coil(addr, desired),
+                        Setpoint::Coil(addr, found),
+                        result,
+                    );
+                    corrections += 1;
+                }
+            }
+        }
+
+        for (addr, cnt) in runs(registers.keys().copied(), self.limits.read_registers) {
+            let found = match self
+                .reconcile_send(Request::ReadHoldingRegisters(addr, cnt))
+                .await?
+            {
+                Ok(Response::ReadHoldingRegisters(found)) => found,
+                Ok(_) => unreachable!("call() should reject mismatching responses"),
+                Err(e) => {
+                    warn!(
+                        "could not read back holding registers {}+{}: {}",
+                        addr, cnt, e
+                    );
+                    continue;
+                }
+            };
+            let addrs = (0..cnt).filter_map(|offset| addr.checked_add(offset));
+            for (addr, found) in addrs.zip(found) {
+                let Some(&desired) = registers.get(&addr) else {
+                    continue;
+                };
+                if found != desired {
+                    let request = Request::WriteSingleRegister(addr, desired);
+                    let result = self.reconcile_send(request).await?;
+                    self.correct(
+                        Setpoint::Register(addr, desired),
+                        Setpoint::Register(addr, found),
+                        result,
+                    );
+                    corrections += 1;
+                }
+            }
+        }
+
+        Ok(corrections)
+    }
+
+    async fn reconcile_send(
+        &self,
+        request: Request<'_>,
+    ) -> Result<Result<Response, ExceptionCode>, ModbusError> {
+        let res = self.send(request).await;
+        if let Err(e) = &res {
+            self.mark_disconnected(DisconnectCause::transport(e)).await;
+        }
+        res
+    }
+
+    fn correct(&self, desired: Setpoint, found: Setpoint, result: Result<Response, ExceptionCode>) {
+        info!("setpoint drifted to {:?}, rewriting {:?}", found, desired);
+        if let (Some(cache), Ok(_)) = (self.cache.get(), &result) {
+            match desired {
+                Setpoint::Coil(addr, coil) => cache.store_coils(addr, &[coil]),
+                Setpoint::Register(addr, word) => cache.store_holding_registers(addr, &[word]),
+            }
+        }
+        if let Some(desired_state) = self.desired_state.get() {
+            let _ = desired_state.sender.send(Correction {
+                desired,
+                found,
+                timestamp: SystemTime::now(),
+                result: result.map(|_| ()).map_err(PollError::Exception),
+            });
+        }
+    }
+
+    /// Additionally reconciles every `period`, reconnecting if the device is unreachable.
+    /// Abort the returned handle to stop.
+    pub fn spawn_reconciler(&self, period: Duration) -> JoinHandle<()> {
+        let ctx = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = interval(period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if !ctx.is_connected() {
+                    // Reconciles on success.
+                    ctx.reconnect().await;
+                } else if let Err(e) = ctx.reconcile().await {
+                    warn!("could not reconcile setpoints: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+
+    #[tokio::test]
+    async fn rewrites_drifted_registers() {
+        let mut image = RegisterImage::new();
+        image.set(10, &[1, 2]);
+        image.set(65_534, &[3, 4]);
+        let ctx = context(image).await;
+        let clone = ctx.clone();
+
+        let desired = ctx.enable_desired_state();
+        let mut corrections = desired.subscribe();
+        desired.set(Setpoint::Register(10, 1));
+        desired.set(Setpoint::Register(11, 5));
+        desired.set(Setpoint::Register(65_535, 6));
+
+        assert_eq!(clone.reconcile().await.unwrap(), 2);
+        let correction = corrections.recv().await.unwrap();
+        assert_eq!(correction.desired, Setpoint::Register(11, 5));
+        assert_eq!(correction.found, Setpoint::Register(11, 2));
+        assert_eq!(clone.reconcile().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_unreadable_registers() {
+        let ctx = context(RegisterImage::new()).await;
+        ctx.enable_desired_state()
+            .set(Setpoint::Register(Address::MAX, 1));
+        assert_eq!(ctx.reconcile().await.unwrap(), 0);
+    }
+}
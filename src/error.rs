@@ -0,0 +1,294 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_modbus::{prelude::*, Error as ModbusError, Result as ModbusResult};
+
+/// Why a request through a [`crate::prelude::RobustContext`] failed.
+///
+/// The [`Reader`](tokio_modbus::client::Reader) and
+/// [`Writer`](tokio_modbus::client::Writer) traits return it wrapped into a transport
+/// error; use [`RobustError::downcast`] to get it back.
+#[derive(Debug, Clone)]
+pub enum RobustError {
+    /// There was no connection and connecting failed.
+    ConnectFailed(Arc<io::Error>),
+    /// The connection timed out, e.g. because TCP keepalive probes went unanswered.
+    Timeout,
+    /// Sending the request failed.
+    Send(Arc<io::Error>),
+    /// Receiving the response failed, or it could not be decoded.
+    Receive(Arc<io::Error>),
+    /// The device answered with an exception, see [`RobustError::flatten`].
+    Exception(ExceptionCode),
+    RetriesExhausted {
+        attempts: u32,
+        last: Box<RobustError>,
+    },
+    /// The circuit breaker rejected the request without sending it.
+    CircuitOpen,
+    /// A background request was shed without being sent because the link is degraded,
+    /// see [`crate::prelude::RobustContext::background_probe_interval`].
+    Cancelled,
+}
+
+impl RobustError {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            RobustError::ConnectFailed(e) | RobustError::Send(e) | RobustError::Receive(e) => {
+                e.kind()
+            }
+            RobustError::Timeout => io::ErrorKind::TimedOut,
+            RobustError::Exception(_) => io::ErrorKind::Other,
+            RobustError::RetriesExhausted { last, .. } => last.kind(),
+            RobustError::CircuitOpen => io::ErrorKind::NotConnected,
+            RobustError::Cancelled => io::ErrorKind::Interrupted,
+        }
+    }
+
+    /// The error of a failed connect, as stored in place of the connection.
+    ///
+    /// Errors stored by [`crate::prelude::RobustContext::reconnect`] are shared as they
+    /// are, others are copied.
+    pub(crate) fn connect_failed(e: &io::Error) -> Self {
+        match e.get_ref().and_then(|inner| inner.downcast_ref()) {
+            Some(e @ RobustError::ConnectFailed(_)) => e.clone(),
+            _ => RobustError::ConnectFailed(Arc::new(io::Error::new(e.kind(), e.to_string()))),
+        }
+    }
+
+    /// Classifies an I/O error that was not tagged by the connection.
+    fn from_io(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut => RobustError::Timeout,
+            io::ErrorKind::BrokenPipe | io::ErrorKind::WriteZero => RobustError::Send(Arc::new(e)),
+            _ => RobustError::Receive(Arc::new(e)),
+        }
+    }
+
+    /// Like [`From<ModbusError>`], for an error that cannot be taken.
+    pub(crate) fn from_ref(e: &ModbusError) -> Self {
+        if let Some(e) = RobustError::downcast(e) {
+            return e.clone();
+        }
+        match e {
+            ModbusError::Transport(e) => {
+                RobustError::from_io(io::Error::new(e.kind(), e.to_string()))
+            }
+            ModbusError::Protocol(e) => RobustError::Receive(Arc::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                e.to_string(),
+            ))),
+        }
+    }
+
+    /// Turns exception responses into [`RobustError::Exception`], for callers that handle
+    /// them like any other failure.
+    pub fn flatten<T>(res: ModbusResult<T>) -> Result<T, RobustError> {
+        match res {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => Err(RobustError::Exception(e)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The error this crate wrapped into `e`, if any.
+    pub fn downcast(e: &ModbusError) -> Option<&RobustError> {
+        match e {
+            ModbusError::Transport(e) => e.get_ref()?.downcast_ref(),
+            ModbusError::Protocol(_) => None,
+        }
+    }
+
+    /// The error of the last attempt.
+    pub fn last(&self) -> &RobustError {
+        match self {
+            RobustError::RetriesExhausted { last, .. } => last.last(),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for RobustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RobustError::ConnectFailed(e) => write!(f, "could not connect: {}", e),
+            RobustError::Timeout => write!(f, "request timed out"),
+            RobustError::Send(e) => write!(f, "could not send request: {}", e),
+            RobustError::Receive(e) => write!(f, "could not receive response: {}", e),
+            RobustError::Exception(e) => write!(f, "exception response: {}", e),
+            RobustError::RetriesExhausted { attempts, last } => {
+                write!(f, "gave up after {} attempts: {}", attempts, last)
+            }
+            RobustError::CircuitOpen => write!(f, "circuit breaker is open"),
+            RobustError::Cancelled => write!(f, "background request cancelled, link degraded"),
+        }
+    }
+}
+
+impl std::error::Error for RobustError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RobustError::ConnectFailed(e) | RobustError::Send(e) | RobustError::Receive(e) => {
+                Some(e.as_ref())
+            }
+            RobustError::RetriesExhausted { last, .. } => Some(last.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<ModbusError> for RobustError {
+    fn from(e: ModbusError) -> Self {
+        match e {
+            ModbusError::Transport(e) => {
+                if e.get_ref().is_some_and(|inner| inner.is::<RobustError>()) {
+                    let inner = e.into_inner().expect("checked above");
+                    return *inner.downcast().expect("checked above");
+                }
+                RobustError::from_io(e)
+            }
+            ModbusError::Protocol(e) => {
+                RobustError::Receive(Arc::new(io::Error::new(io::ErrorKind::InvalidData, e)))
+            }
+        }
+    }
+}
+
+impl From<RobustError> for io::Error {
+    fn from(e: RobustError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+impl From<RobustError> for ModbusError {
+    fn from(e: RobustError) -> Self {
+        ModbusError::Transport(e.into())
+    }
+}
+
+/// A connection that tags its I/O errors with whether they happened while sending or
+/// receiving. The device closing the connection is an error, too.
+#[derive(Debug)]
+pub(crate) struct TaggedStream<S>(pub S);
+
+fn tag(e: io::Error, tag: fn(Arc<io::Error>) -> RobustError) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::TimedOut => RobustError::Timeout.into(),
+        _ => tag(Arc::new(e)).into(),
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TaggedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (filled, remaining) = (buf.filled().len(), buf.remaining());
+        match Pin::new(&mut self.get_mut().0).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) if remaining > 0 && buf.filled().len() == filled => {
+                let e = io::Error::new(io::ErrorKind::UnexpectedEof, "closed by the device");
+                Poll::Ready(Err(tag(e, RobustError::Receive)))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(tag(e, RobustError::Receive))),
+            res => res,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TaggedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0)
+            .poll_write(cx, buf)
+            .map_err(|e| tag(e, RobustError::Send))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0)
+            .poll_flush(cx)
+            .map_err(|e| tag(e, RobustError::Send))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exhausted() -> RobustError {
+        RobustError::RetriesExhausted {
+            attempts: 4,
+            last: Box::new(RobustError::connect_failed(&io::Error::from(
+                io::ErrorKind::ConnectionRefused,
+            ))),
+        }
+    }
+
+    #[test]
+    fn survives_modbus_error() {
+        let e = ModbusError::from(exhausted());
+        let Some(RobustError::RetriesExhausted { attempts, last }) = RobustError::downcast(&e)
+        else {
+            panic!("lost error {e:?}");
+        };
+        assert_eq!(*attempts, 4);
+        assert!(matches!(**last, RobustError::ConnectFailed(_)));
+
+        let e = RobustError::from(e);
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(matches!(e.last(), RobustError::ConnectFailed(_)));
+        assert!(std::error::Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn from_ref_keeps_structure() {
+        let e = ModbusError::from(exhausted());
+        assert!(matches!(
+            RobustError::from_ref(&e),
+            RobustError::RetriesExhausted { attempts: 4, .. }
+        ));
+    }
+
+    #[test]
+    fn classifies_plain_errors() {
+        let timed_out = ModbusError::Transport(io::ErrorKind::TimedOut.into());
+        assert!(matches!(RobustError::from(timed_out), RobustError::Timeout));
+        let reset = ModbusError::Transport(io::ErrorKind::ConnectionReset.into());
+        assert!(matches!(
+            RobustError::from_ref(&reset),
+            RobustError::Receive(e) if e.kind() == io::ErrorKind::ConnectionReset
+        ));
+        let broken = ModbusError::Transport(io::ErrorKind::BrokenPipe.into());
+        assert!(matches!(RobustError::from(broken), RobustError::Send(_)));
+    }
+
+    #[test]
+    fn connect_failed_keeps_source() {
+        let source = Arc::new(io::Error::from(io::ErrorKind::ConnectionRefused));
+        let stored = io::Error::from(RobustError::ConnectFailed(source.clone()));
+        let RobustError::ConnectFailed(e) = RobustError::connect_failed(&stored) else {
+            panic!("not a connect error");
+        };
+        assert!(Arc::ptr_eq(&e, &source));
+    }
+
+    #[test]
+    fn flattens_exceptions() {
+        let res: ModbusResult<()> = Ok(Err(ExceptionCode::IllegalDataAddress));
+        assert!(matches!(
+            RobustError::flatten(res),
+            Err(RobustError::Exception(ExceptionCode::IllegalDataAddress))
+        ));
+        assert!(matches!(RobustError::flatten(Ok(Ok(1))), Ok(1)));
+    }
+}
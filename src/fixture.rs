@@ -0,0 +1,80 @@
+//! A Modbus TCP device on a local port for tests.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_modbus::Address;
+
+/// How the device treats requests on its first connection.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FirstConnection {
+    Answers,
+    /// Answers each request after a delay.
+    Delays(Duration),
+    /// Hangs up when the first request arrives.
+    Closes,
+}
+
+/// Answers every read of a single holding register with the number of the connection it
+/// came in on, counting from 1.
+#[derive(Debug, Clone)]
+pub(crate) struct Device {
+    pub addr: String,
+    connections: Arc<Mutex<u8>>,
+    requests: Arc<Mutex<Vec<(u8, Address)>>>,
+}
+
+impl Device {
+    pub async fn start(first: FirstConnection) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let device = Self {
+            addr: listener.local_addr().unwrap().to_string(),
+            connections: Arc::default(),
+            requests: Arc::default(),
+        };
+
+        let shared = device.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let conn = {
+                    let mut connections = shared.connections.lock().unwrap();
+                    *connections += 1;
+                    *connections
+                };
+                let requests = shared.requests.clone();
+                tokio::spawn(async move {
+                    let mut request = [0; 12];
+                    while stream.read_exact(&mut request).await.is_ok() {
+                        let addr = Address::from_be_bytes([request[8], request[9]]);
+                        requests.lock().unwrap().push((conn, addr));
+                        match first {
+                            FirstConnection::Delays(delay) if conn == 1 => {
+                                tokio::time::sleep(delay).await
+                            }
+                            FirstConnection::Closes if conn == 1 => break,
+                            _ => {}
+                        }
+                        let mut response = request[..4].to_vec();
+                        response.extend([0, 5, request[6], 0x03, 2, 0, conn]);
+                        if stream.write_all(&response).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        device
+    }
+
+    /// Accepted connections so far.
+    pub fn connections(&self) -> u8 {
+        *self.connections.lock().unwrap()
+    }
+
+    /// Connection and start address of every request received so far.
+    pub fn requests(&self) -> Vec<(u8, Address)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{context::RobustContext, poller::PollError, priority::Priority, types::Word};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tokio_modbus::{prelude::*, Address};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeartbeatValue {
+    /// Writes 0, 1, 2, ... wrapping at `u16::MAX`.
+    #[default]
+    Counter,
+    /// Alternates between 0 and 1.
+    Toggle,
+}
+
+impl HeartbeatValue {
+    fn next(&self, word: Word) -> Word {
+        match self {
+            HeartbeatValue::Counter => word.wrapping_add(1),
+            HeartbeatValue::Toggle => word ^ 1,
+        }
+    }
+}
+
+/// A watchdog register that has to be written periodically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    pub addr: Address,
+    pub interval: Duration,
+    pub value: HeartbeatValue,
+}
+
+impl Heartbeat {
+    pub fn new(addr: Address, interval: Duration) -> Self {
+        Self {
+            addr,
+            interval,
+            value: HeartbeatValue::default(),
+        }
+    }
+
+    pub fn with_value(mut self, value: HeartbeatValue) -> Self {
+        self.value = value;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MissReason {
+    Failed(PollError),
+    /// The write succeeded, but later than the interval allows.
+    Late(Duration),
+}
+
+#[derive(Debug, Clone)]
+pub struct MissedHeartbeat {
+    pub timestamp: SystemTime,
+    pub reason: MissReason,
+    /// Heartbeats missed in a row, including this one.
+    pub consecutive: u64,
+}
+
+/// Keeps the heartbeat task running as long as a clone of the context is alive.
+#[derive(Debug)]
+pub(crate) struct HeartbeatGuard {
+    stop: watch::Sender<bool>,
+}
+
+impl HeartbeatGuard {
+    pub fn stop(&self) {
+        let _ = self.stop.send(true);
+    }
+}
+
+async fn run(
+    mut ctx: RobustContext,
+    heartbeat: Heartbeat,
+    mut stop: watch::Receiver<bool>,
+    sender: broadcast::Sender<MissedHeartbeat>,
+) {
+    let mut ticks = interval(heartbeat.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut word = 0;
+    let mut consecutive = 0;
+
+    loop {
+        let deadline = tokio::select! {
+            // Either stopped or the guard was dropped.
+            _ = stop.wait_for(|stopped| *stopped) => break,
+            deadline = ticks.tick() => deadline,
+        };
+
+        let res = ctx.write_single_register(heartbeat.addr, word).await;
+        let late = Instant::now().saturating_duration_since(deadline + heartbeat.interval);
+        let reason = match res {
+            Ok(Ok(())) => {
+                word = heartbeat.value.next(word);
+                if late.is_zero() {
+                    consecutive = 0;
+                    continue;
+                }
+                MissReason::Late(late)
+            }
+            Ok(Err(e)) => MissReason::Failed(PollError::Exception(e)),
+            Err(e) => MissReason::Failed(PollError::Transport(Arc::new(e))),
+        };
+
+        consecutive += 1;
+        warn!(
+            "missed heartbeat on register {} ({} in a row): {:?}",
+            heartbeat.addr, consecutive, reason
+        );
+        let _ = sender.send(MissedHeartbeat {
+            timestamp: SystemTime::now(),
+            reason,
+            consecutive,
+        });
+    }
+}
+
+impl RobustContext {
+    /// Writes the watchdog register on its interval, ahead of all other requests, and
+    /// reports missed heartbeats.
+    ///
+    /// The heartbeat is shared by all clones of this context. It stops when the last
+    /// clone is dropped, when any clone is disconnected, or when another heartbeat is
+    /// started on any clone. It keeps going while the link is down, reconnecting like
+    /// any other request.
+    pub fn start_heartbeat(&self, heartbeat: Heartbeat) -> broadcast::Receiver<MissedHeartbeat> {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let (sender, receiver) = broadcast::channel(16);
+        let mut ctx = self.with_priority(Priority::Critical);
+        // The task must not keep itself alive.
+        ctx.heartbeat = Arc::default();
+        tokio::spawn(run(ctx, heartbeat, stop_receiver, sender));
+
+        let guard = HeartbeatGuard { stop: stop_sender };
+        if let Some(previous) = self.heartbeat_guard().replace(guard) {
+            previous.stop();
+        }
+        receiver
+    }
+
+    /// Stops the heartbeat of this context and its clones, if one is running.
+    pub(crate) fn stop_heartbeat(&self) {
+        if let Some(guard) = self.heartbeat_guard().take() {
+            guard.stop();
+        }
+    }
+
+    fn heartbeat_guard(&self) -> std::sync::MutexGuard<'_, Option<HeartbeatGuard>> {
+        self.heartbeat.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+    use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+    use tokio::time::sleep;
+
+    async fn register(ctx: &mut RobustContext) -> Word {
+        ctx.read_holding_registers(100, 1).await.unwrap().unwrap()[0]
+    }
+
+    async fn watchdog() -> RobustContext {
+        let mut image = RegisterImage::new();
+        image.set(100, &[Word::MAX]);
+        context(image).await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn writes_on_interval() {
+        let mut ctx = watchdog().await;
+        let heartbeat = Heartbeat::new(100, Duration::from_secs(1));
+        let _missed = ctx.start_heartbeat(heartbeat.with_value(HeartbeatValue::Counter));
+
+        sleep(Duration::from_millis(2500)).await;
+        assert_eq!(register(&mut ctx).await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_when_earlier_clone_disconnects() {
+        let ctx = watchdog().await;
+        let mut clone = ctx.clone();
+        let mut missed = ctx.start_heartbeat(Heartbeat::new(100, Duration::from_secs(1)));
+
+        clone.disconnect().await.unwrap();
+        assert!(matches!(missed.recv().await, Err(RecvError::Closed)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replaced_from_another_clone() {
+        let ctx = watchdog().await;
+        let mut missed = ctx.start_heartbeat(Heartbeat::new(100, Duration::from_secs(1)));
+
+        let mut clone = ctx.clone();
+        let _replacement = clone.start_heartbeat(
+            Heartbeat::new(100, Duration::from_secs(1)).with_value(HeartbeatValue::Toggle),
+        );
+        assert!(matches!(missed.recv().await, Err(RecvError::Closed)));
+
+        sleep(Duration::from_millis(1500)).await;
+        assert_eq!(register(&mut clone).await, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_when_last_clone_dropped() {
+        let ctx = watchdog().await;
+        let mut missed = ctx.start_heartbeat(Heartbeat::new(100, Duration::from_secs(1)));
+        let clone = ctx.clone();
+
+        drop(ctx);
+        sleep(Duration::from_millis(1500)).await;
+        assert!(matches!(missed.try_recv(), Err(TryRecvError::Empty)));
+        drop(clone);
+        assert!(matches!(missed.recv().await, Err(RecvError::Closed)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_missed_heartbeats() {
+        let ctx = context(RegisterImage::new()).await;
+        let mut missed = ctx.start_heartbeat(Heartbeat::new(100, Duration::from_secs(1)));
+
+        for consecutive in 1..=2 {
+            let miss = missed.recv().await.unwrap();
+            assert_eq!(miss.consecutive, consecutive);
+            assert!(matches!(
+                miss.reason,
+                MissReason::Failed(PollError::Exception(ExceptionCode::IllegalDataAddress))
+            ));
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{context::RobustContext, error::RobustError};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use tokio_modbus::{prelude::*, Error as ModbusError, Result as ModbusResult};
+use tracing::warn;
+
+/// Runs on every fresh connection before it serves requests.
+pub type ConnectHook = Arc<
+    dyn for<'a> Fn(
+            &'a mut client::Context,
+        ) -> Pin<Box<dyn Future<Output = ModbusResult<()>> + Send + 'a>>
+        + Send
+        + Sync,
+>;
+
+/// Runs whenever an established connection is lost.
+pub type DisconnectHook =
+    Arc<dyn Fn(DisconnectCause) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub enum DisconnectCause {
+    /// A request failed on the transport.
+    Transport(RobustError),
+    /// The context was disconnected explicitly.
+    Disconnected,
+    /// A request was dropped while awaiting its response, so the connection was closed
+    /// to discard the late response.
+    Cancelled,
+}
+
+impl DisconnectCause {
+    pub(crate) fn transport(e: &ModbusError) -> Self {
+        DisconnectCause::Transport(RobustError::from_ref(e))
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    on_connect: RwLock<Vec<ConnectHook>>,
+    on_disconnect: RwLock<Vec<DisconnectHook>>,
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_connect", &self.on_connect().len())
+            .field("on_disconnect", &self.on_disconnect().len())
+            .finish()
+    }
+}
+
+impl Hooks {
+    fn on_connect(&self) -> Vec<ConnectHook> {
+        self.on_connect
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn on_disconnect(&self) -> Vec<DisconnectHook> {
+        self.on_disconnect
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Runs the connect hooks in order of registration, stopping at the first failure.
+    pub async fn connected(&self, ctx: &mut client::Context) -> io::Result<()> {
+        for hook in self.on_connect() {
+            match hook(ctx).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        RobustError::Exception(e),
+                    ))
+                }
+                Err(e) => {
+                    let kind = match &e {
+                        ModbusError::Transport(e) => e.kind(),
+                        ModbusError::Protocol(_) => io::ErrorKind::ConnectionAborted,
+                    };
+                    return Err(io::Error::new(kind, format!("connect hook failed: {}", e)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RobustContext {
+    /// Registers a hook that runs on every fresh connection, e.g. to unlock the device or
+    /// select a register bank. It runs before any queued request; if it fails, the
+    /// connection is dropped and the connect attempt counts as failed.
+    ///
+    /// Hooks are shared by all clones of this context.
+    pub fn on_connect<F>(&self, hook: F)
+    where
+        F: for<'a> Fn(
+                &'a mut client::Context,
+            ) -> Pin<Box<dyn Future<Output = ModbusResult<()>> + Send + 'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks
+            .on_connect
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::new(hook));
+    }
+
+    /// Registers a hook that runs with the cause whenever an established connection is
+    /// lost.
+    pub fn on_disconnect<F>(&self, hook: F)
+    where
+        F: Fn(DisconnectCause) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        self.hooks
+            .on_disconnect
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::new(hook));
+    }
+
+    /// Marks the connection as lost, running the disconnect hooks if it was up.
+    pub(crate) async fn mark_disconnected(&self, cause: DisconnectCause) {
+        if self.set_disconnected(&cause) {
+            self.run_disconnect_hooks(cause).await;
+        }
+    }
+
+    /// Marks the connection as lost, returning whether it was up.
+    pub(crate) fn set_disconnected(&self, cause: &DisconnectCause) -> bool {
+        if !self.connected.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        self.stats.record_disconnect();
+        warn!("modbus connection lost: {:?}", cause);
+        true
+    }
+
+    pub(crate) async fn run_disconnect_hooks(&self, cause: DisconnectCause) {
+        for hook in self.hooks.on_disconnect() {
+            hook(cause.clone()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{Device, FirstConnection};
+    use std::sync::Mutex;
+
+    fn record_causes(ctx: &RobustContext) -> Arc<Mutex<Vec<DisconnectCause>>> {
+        let causes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = causes.clone();
+        ctx.on_disconnect(move |cause| {
+            recorded.lock().unwrap().push(cause);
+            Box::pin(async {})
+        });
+        causes
+    }
+
+    #[tokio::test]
+    async fn failing_connect_hook_aborts_connect() {
+        let device = Device::start(FirstConnection::Answers).await;
+        let mut ctx = RobustContext::new(&device.addr, Slave(1)).await.unwrap();
+        ctx.on_connect(|_| Box::pin(async { Ok(Err(ExceptionCode::IllegalFunction)) }));
+
+        ctx.reconnect().await;
+        assert!(!ctx.is_connected());
+        assert!(device.connections() > 1);
+        assert!(ctx.read_holding_registers(0, 1).await.is_err());
+        assert!(device.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn connect_hook_runs_before_first_request() {
+        let device = Device::start(FirstConnection::Answers).await;
+        let mut ctx = RobustContext::new(&device.addr, Slave(1)).await.unwrap();
+        ctx.on_connect(|ctx| {
+            Box::pin(async move {
+                ctx.read_holding_registers(1, 1)
+                    .await
+                    .map(|res| res.map(drop))
+            })
+        });
+
+        ctx.reconnect().await;
+        ctx.read_holding_registers(0, 1).await.unwrap().unwrap();
+        assert_eq!(device.requests(), vec![(1, 1), (1, 0)]);
+    }
+
+    #[tokio::test]
+    async fn disconnect_hook_receives_cause() {
+        let device = Device::start(FirstConnection::Closes).await;
+        let mut ctx = RobustContext::new(&device.addr, Slave(1)).await.unwrap();
+        let causes = record_causes(&ctx);
+
+        ctx.reconnect().await;
+        let words = ctx.read_holding_registers(0, 1).await.unwrap().unwrap();
+        assert_eq!(words, vec![2]);
+        ctx.disconnect().await.unwrap();
+
+        let causes = causes.lock().unwrap();
+        assert!(
+            matches!(
+                causes.as_slice(),
+                [
+                    DisconnectCause::Transport(RobustError::Receive(_)),
+                    DisconnectCause::Disconnected
+                ]
+            ),
+            "{causes:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_hook_not_run_without_connection() {
+        let device = Device::start(FirstConnection::Answers).await;
+        let ctx = RobustContext::new(&device.addr, Slave(1)).await.unwrap();
+        let causes = record_causes(&ctx);
+
+        ctx.mark_disconnected(DisconnectCause::Disconnected).await;
+        assert!(causes.lock().unwrap().is_empty());
+    }
+}
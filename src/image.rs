@@ -0,0 +1,170 @@
+use crate::types::Word;
+use std::collections::BTreeMap;
+use std::io;
+use tokio_modbus::{prelude::*, Address, Quantity, Result as ModbusResult};
+
+/// An in-memory holding register table that answers like a device.
+///
+/// Reading or writing an address that was never set fails with `IllegalDataAddress`.
+/// Convert it into a [`client::Context`] to use it wherever a [`Reader`] is expected.
+#[derive(Debug, Clone, Default)]
+pub struct RegisterImage {
+    slave: Option<Slave>,
+    registers: BTreeMap<Address, Word>,
+}
+
+impl RegisterImage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Words beyond the last address are ignored.
+    pub fn set(&mut self, addr: Address, words: &[Word]) {
+        for (offset, word) in (0..=Address::MAX).zip(words) {
+            let Some(addr) = addr.checked_add(offset) else {
+                break;
+            };
+            self.registers.insert(addr, *word);
+        }
+    }
+
+    pub fn get(&self, addr: Address, cnt: Quantity) -> Option<Vec<Word>> {
+        (0..cnt)
+            .map(|offset| {
+                addr.checked_add(offset)
+                    .and_then(|addr| self.registers.get(&addr).copied())
+            })
+            .collect()
+    }
+
+    fn write(&mut self, addr: Address, words: &[Word]) -> Result<(), ExceptionCode> {
+        let cnt = Quantity::try_from(words.len()).map_err(|_| ExceptionCode::IllegalDataValue)?;
+        self.get(addr, cnt)
+            .ok_or(ExceptionCode::IllegalDataAddress)?;
+        self.set(addr, words);
+        Ok(())
+    }
+
+    fn handle(&mut self, request: Request<'_>) -> Result<Response, ExceptionCode> {
+        match request {
+            Request::ReadHoldingRegisters(addr, cnt) => self
+                .get(addr, cnt)
+                .map(Response::ReadHoldingRegisters)
+                .ok_or(ExceptionCode::IllegalDataAddress),
+            Request::WriteSingleRegister(addr, word) => self
+                .write(addr, &[word])
+                .map(|_| Response::WriteSingleRegister(addr, word)),
+            Request::WriteMultipleRegisters(addr, words) => self
+                .write(addr, &words)
+                .map(|_| Response::WriteMultipleRegisters(addr, words.len() as Quantity)),
+            Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                let word = self.get(addr, 1).ok_or(ExceptionCode::IllegalDataAddress)?[0];
+                self.write(addr, &[(word & and_mask) | (or_mask & !and_mask)])
+                    .map(|_| Response::MaskWriteRegister(addr, and_mask, or_mask))
+            }
+            _ => Err(ExceptionCode::IllegalFunction),
+        }
+    }
+}
+
+impl From<RegisterImage> for client::Context {
+    fn from(image: RegisterImage) -> Self {
+        client::Context::from(Box::new(image) as Box<dyn Client>)
+    }
+}
+
+impl SlaveContext for RegisterImage {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = Some(slave);
+    }
+}
+
+impl Client for RegisterImage {
+    #[doc = " Invoke a _Modbus_ function"]
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn call<'life0, 'life1, 'async_trait>(
+        &'life0 mut self,
+        request: Request<'life1>,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = ModbusResult<Response>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        'life1: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { Ok(self.handle(request)) })
+    }
+
+    #[doc = " Disconnects the client."]
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn disconnect<'life0, 'async_trait>(
+        &'life0 mut self,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = io::Result<()>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A [`RobustContext`] that is connected to `image` instead of a device.
+#[cfg(test)]
+pub(crate) async fn context(image: RegisterImage) -> crate::context::RobustContext {
+    let ctx = crate::context::RobustContext::new("127.0.0.1:502", Slave(1))
+        .await
+        .unwrap();
+    *ctx.ctx.lock().await = Ok(image.into());
+    ctx.connected
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    ctx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_stops_at_last_address() {
+        let mut image = RegisterImage::new();
+        image.set(65_534, &[1, 2, 3]);
+        assert_eq!(image.get(65_534, 2), Some(vec![1, 2]));
+        assert_eq!(image.get(0, 1), None);
+    }
+
+    #[tokio::test]
+    async fn answers_like_a_device() {
+        let mut image = RegisterImage::new();
+        image.set(100, &[1, 2]);
+        let mut ctx = client::Context::from(image);
+
+        ctx.write_single_register(101, 0x00ff)
+            .await
+            .unwrap()
+            .unwrap();
+        ctx.masked_write_register(100, 0xfffe, 0x0000)
+            .await
+            .unwrap()
+            .unwrap();
+        let words = ctx.read_holding_registers(100, 2).await.unwrap().unwrap();
+        assert_eq!(words, vec![0, 0x00ff]);
+
+        let err = ctx
+            .read_holding_registers(101, 2)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err, ExceptionCode::IllegalDataAddress);
+    }
+}
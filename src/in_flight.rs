@@ -0,0 +1,118 @@
+use crate::{context::RobustContext, hooks::DisconnectCause};
+use std::io;
+use std::sync::atomic::Ordering;
+use tokio::runtime::Handle;
+use tokio::sync::MutexGuard;
+use tokio_modbus::prelude::*;
+
+/// Holds the connection while a request awaits its response.
+///
+/// If the request future is dropped before the response was read, the response may still
+/// arrive and would be taken as the answer to the next request. The connection is closed
+/// instead, and the next request reconnects before it is sent.
+pub(crate) struct InFlight<'a> {
+    context: &'a RobustContext,
+    ctx: MutexGuard<'a, io::Result<client::Context>>,
+    done: bool,
+}
+
+impl<'a> InFlight<'a> {
+    pub fn new(
+        context: &'a RobustContext,
+        ctx: MutexGuard<'a, io::Result<client::Context>>,
+    ) -> Self {
+        Self {
+            context,
+            ctx,
+            done: false,
+        }
+    }
+
+    pub fn ctx(&mut self) -> &mut io::Result<client::Context> {
+        &mut self.ctx
+    }
+
+    /// Marks the response as read.
+    pub fn complete(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.done || self.ctx.is_err() {
+            return;
+        }
+
+        *self.ctx = Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "connection closed after a cancelled request",
+        ));
+        self.context.abandoned.store(true, Ordering::Relaxed);
+        if !self.context.set_disconnected(&DisconnectCause::Cancelled) {
+            return;
+        }
+        // Hooks are async, so they run on a task of their own.
+        if let Ok(handle) = Handle::try_current() {
+            let context = self.context.clone();
+            handle.spawn(async move {
+                context
+                    .run_disconnect_hooks(DisconnectCause::Cancelled)
+                    .await;
+            });
+        }
+    }
+}
+
+impl RobustContext {
+    /// Reconnects if the connection was closed after a cancelled request.
+    pub(crate) async fn reconnect_abandoned(&self) {
+        if self.abandoned.swap(false, Ordering::Relaxed) {
+            self.reconnect().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{Device, FirstConnection};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    async fn slow_first_connection() -> String {
+        Device::start(FirstConnection::Delays(Duration::from_millis(200)))
+            .await
+            .addr
+    }
+    #[tokio::test]
+    async fn dropped_request_does_not_desync_next_read() {
+        let mut ctx = RobustContext::new(&slow_first_connection().await, Slave(1))
+            .await
+            .unwrap();
+        ctx.reconnect().await;
+        let read = ctx.read_holding_registers(0, 1);
+        assert!(timeout(Duration::from_millis(50), read).await.is_err());
+        assert!(!ctx.is_connected());
+
+        // Waits past the late response of the first connection.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let words = ctx.read_holding_registers(0, 1).await.unwrap().unwrap();
+        assert_eq!(words, vec![2]);
+        let stats = ctx.stats().functions[&0x03];
+        assert_eq!((stats.successes, stats.failures), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn client_call_reconnects_after_dropped_request() {
+        let mut ctx = RobustContext::new(&slow_first_connection().await, Slave(1))
+            .await
+            .unwrap();
+        ctx.reconnect().await;
+        let read = ctx.call(Request::ReadHoldingRegisters(0, 1));
+        assert!(timeout(Duration::from_millis(50), read).await.is_err());
+
+        let response = ctx.call(Request::ReadHoldingRegisters(0, 1)).await;
+        assert!(matches!(response, Ok(Ok(Response::ReadHoldingRegisters(words))) if words == [2]));
+    }
+}
@@ -1,11 +1,57 @@
+pub mod bitfield;
+pub mod breaker;
+pub mod cache;
+pub mod capture;
+mod chunk;
+pub mod coalesce;
+pub mod codec;
 mod context;
+pub mod cov;
+pub mod desired;
+mod error;
+#[cfg(test)]
+mod fixture;
+pub mod heartbeat;
+pub mod hooks;
+pub mod image;
+mod in_flight;
+pub mod liveness;
+mod metrics;
+mod outage;
+pub mod point;
+pub mod poller;
+mod priority;
+mod rate_limit;
 mod reader;
+mod single_flight;
+pub mod stats;
+pub mod sunspec;
 mod try_read;
 mod try_write;
 mod types;
+pub mod write_queue;
 mod writer;
 
 pub mod prelude {
+    pub use crate::bitfield::{Bitfield, EnumRegister, RegisterEnum};
+    pub use crate::breaker::{CircuitBreaker, CircuitState};
+    pub use crate::cache::{Cached, Quality};
+    pub use crate::capture::Capture;
+    pub use crate::chunk::PduLimits;
+    pub use crate::coalesce::{ReadCoalescer, RegisterTable};
+    pub use crate::codec::{ByteOrder, DateTime, DateTimeLayout, Padding};
     pub use crate::context::RobustContext;
+    pub use crate::cov::{Change, Deadband, WatchPoint};
+    pub use crate::desired::{Correction, DesiredState, Setpoint};
+    pub use crate::error::RobustError;
+    pub use crate::heartbeat::{Heartbeat, HeartbeatValue, MissedHeartbeat};
+    pub use crate::hooks::DisconnectCause;
+    pub use crate::liveness::{LivenessProbe, TcpOptions};
+    pub use crate::point::{DataType, Point, ScaleFactor};
+    pub use crate::poller::{PollEvent, PollGroup, PollRead, Poller};
+    pub use crate::priority::Priority;
+    pub use crate::rate_limit::RateLimit;
+    pub use crate::stats::Stats;
+    pub use crate::write_queue::{QueuedWrite, WriteOutcome};
     pub use tokio_modbus::prelude::*;
 }
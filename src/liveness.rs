@@ -0,0 +1,198 @@
+use crate::{context::RobustContext, hooks::DisconnectCause, poller::PollRead};
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::sleep_until;
+use tokio_modbus::prelude::*;
+use tracing::debug;
+
+/// Socket options applied to every new connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TcpOptions {
+    /// Disables Nagle's algorithm, so requests are sent without delay.
+    pub nodelay: bool,
+    /// Enables SO_KEEPALIVE with the given idle time before the first probe.
+    pub keepalive: Option<Duration>,
+}
+
+impl TcpOptions {
+    pub(crate) fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        if let Some(time) = self.keepalive {
+            SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shortest idle time, so the probe cannot spin.
+const MIN_IDLE: Duration = Duration::from_millis(100);
+
+/// Longest time between reconnects while the device stays unreachable.
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A cheap read that checks an idle connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LivenessProbe {
+    /// Time without a response after which the probe is sent, at least 100 ms.
+    pub idle: Duration,
+    pub read: PollRead,
+}
+
+impl LivenessProbe {
+    pub fn new(idle: Duration, read: PollRead) -> Self {
+        Self { idle, read }
+    }
+
+    fn request(&self) -> Request<'static> {
+        match self.read {
+            PollRead::Coils(addr, cnt) => Request::ReadCoils(addr, cnt),
+            PollRead::DiscreteInputs(addr, cnt) => Request::ReadDiscreteInputs(addr, cnt),
+            PollRead::HoldingRegisters(addr, cnt) => Request::ReadHoldingRegisters(addr, cnt),
+            PollRead::InputRegisters(addr, cnt) => Request::ReadInputRegisters(addr, cnt),
+        }
+    }
+}
+
+impl RobustContext {
+    pub fn tcp_options(&self) -> TcpOptions {
+        *self.tcp_options.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets the socket options of this context and its clones, starting with the next
+    /// connect.
+    pub fn set_tcp_options(&self, options: TcpOptions) {
+        *self.tcp_options.lock().unwrap_or_else(|e| e.into_inner()) = options;
+    }
+
+    /// Sends `probe` whenever the device has not responded for `probe.idle`, and
+    /// reconnects if it fails, so a dropped connection is detected before the next user
+    /// request runs into it. Abort the returned handle to stop.
+    ///
+    /// While the device stays unreachable, the time between reconnects doubles up to a
+    /// minute. Probes and reconnects count towards the circuit breaker and are skipped
+    /// while it is open.
+    pub fn spawn_liveness_probe(&self, probe: LivenessProbe) -> JoinHandle<()> {
+        let ctx = self.clone();
+        let idle = probe.idle.max(MIN_IDLE);
+        tokio::spawn(async move {
+            let mut backoff = idle;
+            loop {
+                let deadline = if ctx.is_connected() {
+                    ctx.last_response() + idle
+                } else {
+                    Instant::now() + backoff
+                };
+                sleep_until(deadline.into()).await;
+                if ctx.is_connected() && ctx.last_response().elapsed() < idle {
+                    continue;
+                }
+                let Some(admission) = ctx.breaker.admit() else {
+                    continue;
+                };
+
+                if ctx.is_connected() {
+                    debug!("probing idle modbus connection");
+                    // Exceptions still prove that the device is alive.
+                    let res = ctx.send(probe.request()).await;
+                    admission.record(res.is_ok());
+                    if let Err(e) = res {
+                        ctx.mark_disconnected(DisconnectCause::transport(&e)).await;
+                        ctx.reconnect().await;
+                    }
+                } else {
+                    ctx.reconnect().await;
+                    admission.record(ctx.is_connected());
+                }
+
+                backoff = if ctx.is_connected() {
+                    idle
+                } else {
+                    backoff
+                        .saturating_mul(2)
+                        .min(MAX_RECONNECT_INTERVAL.max(idle))
+                };
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breaker::CircuitBreaker;
+    use crate::fixture::{Device, FirstConnection};
+    use crate::image::{context, RegisterImage};
+
+    #[tokio::test]
+    async fn zero_idle_does_not_spin() {
+        let mut image = RegisterImage::new();
+        image.set(0, &[0]);
+        let ctx = context(image).await;
+
+        let read = PollRead::HoldingRegisters(0, 1);
+        let probe = ctx.spawn_liveness_probe(LivenessProbe::new(Duration::ZERO, read));
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        probe.abort();
+
+        // A spinning probe would have sent thousands.
+        let probes = ctx.stats().functions[&0x03].successes;
+        assert!((1..=4).contains(&probes), "{probes} probes");
+    }
+
+    /// A context whose every connect attempt reaches `device` but fails.
+    async fn unreachable(device: &Device) -> RobustContext {
+        let ctx = RobustContext::new(&device.addr, Slave(1)).await.unwrap();
+        ctx.on_connect(|_| Box::pin(async { Ok(Err(ExceptionCode::IllegalFunction)) }));
+        ctx
+    }
+
+    fn probe() -> LivenessProbe {
+        LivenessProbe::new(MIN_IDLE, PollRead::HoldingRegisters(0, 1))
+    }
+
+    #[tokio::test]
+    async fn tcp_options_shared_by_clones() {
+        let ctx = RobustContext::new("127.0.0.1:502", Slave(1)).await.unwrap();
+        let clone = ctx.clone();
+        let options = TcpOptions {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(30)),
+        };
+        ctx.set_tcp_options(options);
+        assert_eq!(clone.tcp_options(), options);
+    }
+
+    #[tokio::test]
+    async fn backs_off_while_unreachable() {
+        let device = Device::start(FirstConnection::Answers).await;
+        let ctx = unreachable(&device).await;
+
+        let probe = ctx.spawn_liveness_probe(probe());
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        probe.abort();
+
+        // Reconnects after 100, 300 and 700 ms, with 4 connect attempts each. Without
+        // backoff there would be about 5.
+        let attempts = device.connections();
+        assert!((4..=8).contains(&attempts), "{attempts} connect attempts");
+    }
+
+    #[tokio::test]
+    async fn skipped_while_circuit_open() {
+        let device = Device::start(FirstConnection::Answers).await;
+        let ctx = unreachable(&device).await;
+        ctx.set_circuit_breaker(Some(CircuitBreaker {
+            failure_threshold: 1,
+            open_for: Duration::from_secs(3600),
+        }));
+
+        let probe = ctx.spawn_liveness_probe(probe());
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        probe.abort();
+
+        assert_eq!(device.connections(), 4);
+    }
+}
@@ -0,0 +1,71 @@
+//! Request, retry and reconnect metrics, recorded through the `metrics` facade when the
+//! `metrics` feature is enabled.
+
+use std::time::Duration;
+use tokio_modbus::{prelude::*, Error as ModbusError, FunctionCode, Result as ModbusResult};
+
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+fn outcome(res: &ModbusResult<Response>) -> (&'static str, Option<ExceptionCode>) {
+    match res {
+        Ok(Ok(_)) => ("ok", None),
+        Ok(Err(e)) => ("exception", Some(*e)),
+        Err(ModbusError::Transport(e)) if e.kind() == std::io::ErrorKind::TimedOut => {
+            ("timeout", None)
+        }
+        Err(_) => ("transport", None),
+    }
+}
+
+/// Records the outcome and latency of a single request on the wire.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_request(
+    host: &str,
+    slave: Slave,
+    function: FunctionCode,
+    res: &ModbusResult<Response>,
+    latency: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [
+            ("host", host.to_string()),
+            ("unit", slave.0.to_string()),
+            ("function", format!("{:#04x}", function.value())),
+        ];
+        let (outcome, exception) = outcome(res);
+        let mut outcome_labels = labels.to_vec();
+        outcome_labels.push(("outcome", outcome.to_string()));
+        if let Some(exception) = exception {
+            outcome_labels.push(("exception", format!("{:?}", exception)));
+        }
+
+        ::metrics::counter!("modbus_requests_total", &outcome_labels).increment(1);
+        ::metrics::histogram!("modbus_request_duration_seconds", &labels)
+            .record(latency.as_secs_f64());
+    }
+}
+
+/// Records that a command is retried.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_retry(host: &str, slave: Slave) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(
+        "modbus_retries_total",
+        "host" => host.to_string(),
+        "unit" => slave.0.to_string()
+    )
+    .increment(1);
+}
+
+/// Records a single connect attempt.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_connect(host: &str, slave: Slave, success: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("host", host.to_string()), ("unit", slave.0.to_string())];
+        ::metrics::counter!("modbus_reconnect_attempts_total", &labels).increment(1);
+        if success {
+            ::metrics::counter!("modbus_reconnects_total", &labels).increment(1);
+        }
+    }
+}
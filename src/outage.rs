@@ -0,0 +1,133 @@
+use crate::context::RobustContext;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+#[derive(Debug)]
+struct Outage {
+    since: Instant,
+    attempts: u64,
+    last_summary: Instant,
+}
+
+/// Logs failed reconnects once per outage, with periodic summaries while it lasts.
+#[derive(Debug)]
+pub(crate) struct OutageLog {
+    outage: Mutex<Option<Outage>>,
+    summary_interval: Mutex<Duration>,
+}
+
+impl Default for OutageLog {
+    fn default() -> Self {
+        Self {
+            outage: Mutex::default(),
+            summary_interval: Mutex::new(Duration::from_secs(5 * 60)),
+        }
+    }
+}
+
+impl OutageLog {
+    fn summary_interval(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.summary_interval
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a reconnect that failed after `attempts` connect attempts.
+    pub fn failed(&self, e: &io::Error, attempts: u64) {
+        let summary_interval = *self.summary_interval();
+        let mut outage = self.outage.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        match outage.as_mut() {
+            None => {
+                error!("could not reconnect modbus, link is down: {}", e);
+                *outage = Some(Outage {
+                    since: now,
+                    attempts,
+                    last_summary: now,
+                });
+            }
+            Some(outage) => {
+                outage.attempts += attempts;
+                if now - outage.last_summary >= summary_interval {
+                    outage.last_summary = now;
+                    warn!(
+                        "modbus still down after {}, {} attempts: {}",
+                        format_duration(now - outage.since),
+                        outage.attempts,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Records a reconnect that succeeded after `failed` failed connect attempts.
+    pub fn recovered(&self, failed: u64) {
+        let outage = self.outage.lock().unwrap_or_else(|e| e.into_inner()).take();
+        match outage {
+            Some(outage) => info!(
+                "modbus link restored after {}, {} failed attempts",
+                format_duration(outage.since.elapsed()),
+                outage.attempts + failed
+            ),
+            None => info!("successfully reconnected modbus"),
+        }
+    }
+}
+
+impl RobustContext {
+    pub fn outage_summary_interval(&self) -> Duration {
+        *self.outage_log.summary_interval()
+    }
+
+    /// How often a summary is logged while reconnecting keeps failing, for this context
+    /// and all its clones. Defaults to 5 minutes.
+    pub fn set_outage_summary_interval(&self, interval: Duration) {
+        *self.outage_log.summary_interval() = interval;
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{} s", secs),
+        60..=3599 => format!("{} min", secs / 60),
+        _ => format!("{} h {} min", secs / 3600, secs / 60 % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempts(log: &OutageLog) -> Option<u64> {
+        log.outage
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|outage| outage.attempts)
+    }
+
+    #[test]
+    fn counts_attempts_until_recovery() {
+        let log = OutageLog::default();
+        let e = io::ErrorKind::ConnectionRefused.into();
+        log.failed(&e, 4);
+        log.failed(&e, 4);
+        assert_eq!(attempts(&log), Some(8));
+        log.recovered(1);
+        assert_eq!(attempts(&log), None);
+    }
+
+    #[test]
+    fn formats_outage_duration() {
+        assert_eq!(format_duration(Duration::from_secs(59)), "59 s");
+        assert_eq!(format_duration(Duration::from_secs(35 * 60 + 59)), "35 min");
+        assert_eq!(
+            format_duration(Duration::from_secs(26 * 3600 + 120)),
+            "26 h 2 min"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    coalesce::{ReadCoalescer, RegisterTable},
+    context::RobustContext,
+    types::Word,
+};
+use std::collections::BTreeMap;
+use std::io;
+use tokio_modbus::{prelude::*, Address, Error as ModbusError, Quantity, Result as ModbusResult};
+
+/// SunSpec marks an unimplemented scale factor register with this value.
+pub const SCALE_FACTOR_NOT_IMPLEMENTED: i16 = i16::MIN;
+
+/// Register layout of a point. Multi-register types are big-endian (high word first).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl DataType {
+    pub fn word_count(self) -> Quantity {
+        match self {
+            DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+        }
+    }
+
+    fn decode(self, words: &[Word]) -> f64 {
+        let dword = || (u32::from(words[0]) << 16) | u32::from(words[1]);
+        match self {
+            DataType::U16 => f64::from(words[0]),
+            DataType::I16 => f64::from(words[0] as i16),
+            DataType::U32 => f64::from(dword()),
+            DataType::I32 => f64::from(dword() as i32),
+            DataType::F32 => f64::from(f32::from_bits(dword())),
+        }
+    }
+
+    fn encode(self, raw: f64) -> io::Result<Vec<Word>> {
+        let (min, max) = match self {
+            DataType::U16 => (0.0, f64::from(u16::MAX)),
+            DataType::I16 => (f64::from(i16::MIN), f64::from(i16::MAX)),
+            DataType::U32 => (0.0, f64::from(u32::MAX)),
+            DataType::I32 => (f64::from(i32::MIN), f64::from(i32::MAX)),
+            DataType::F32 => (f64::from(f32::MIN), f64::from(f32::MAX)),
+        };
+        if !raw.is_finite() || raw < min || raw > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("raw value {raw} out of range for {self:?}"),
+            ));
+        }
+
+        let split = |dword: u32| vec![(dword >> 16) as Word, dword as Word];
+        Ok(match self {
+            DataType::U16 => vec![raw as u16],
+            DataType::I16 => vec![raw as i16 as Word],
+            DataType::U32 => split(raw as u32),
+            DataType::I32 => split(raw as i32 as u32),
+            DataType::F32 => split((raw as f32).to_bits()),
+        })
+    }
+}
+
+/// Power of ten applied to the raw register value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFactor {
+    Static(i16),
+    /// The scale factor is read from a signed holding register, as done by SunSpec.
+    Register(Address),
+}
+
+/// A holding register value converted as `raw * 10^sf + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub addr: Address,
+    pub data_type: DataType,
+    pub scale_factor: ScaleFactor,
+    pub offset: f64,
+}
+
+impl Point {
+    pub fn new(addr: Address, data_type: DataType) -> Self {
+        Self {
+            addr,
+            data_type,
+            scale_factor: ScaleFactor::Static(0),
+            offset: 0.0,
+        }
+    }
+
+    pub fn with_scale_factor(mut self, sf: i16) -> Self {
+        self.scale_factor = ScaleFactor::Static(sf);
+        self
+    }
+
+    pub fn with_scale_factor_register(mut self, addr: Address) -> Self {
+        self.scale_factor = ScaleFactor::Register(addr);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Registers touched by this point, including a dynamic scale factor register.
+    fn span(&self) -> (Address, u32) {
+        let end = u32::from(self.addr) + u32::from(self.data_type.word_count());
+        match self.scale_factor {
+            ScaleFactor::Static(_) => (self.addr, end),
+            ScaleFactor::Register(sf_addr) => {
+                (self.addr.min(sf_addr), end.max(u32::from(sf_addr) + 1))
+            }
+        }
+    }
+
+    /// Addresses of the point and its scale factor register.
+    fn registers(&self) -> impl Iterator<Item = Address> {
+        let sf_addr = match self.scale_factor {
+            ScaleFactor::Static(_) => None,
+            ScaleFactor::Register(sf_addr) => Some(sf_addr),
+        };
+        let addr = self.addr;
+        (0..self.data_type.word_count())
+            .filter_map(move |offset| addr.checked_add(offset))
+            .chain(sf_addr)
+    }
+
+    /// Decodes the point from registers read by address, if all of them were read.
+    fn decode_registers(&self, registers: &BTreeMap<Address, Word>) -> Option<f64> {
+        let words = (0..self.data_type.word_count())
+            .map(|offset| registers.get(&self.addr.checked_add(offset)?).copied())
+            .collect::<Option<Vec<_>>>()?;
+        let sf = match self.scale_factor {
+            ScaleFactor::Static(sf) => sf,
+            ScaleFactor::Register(sf_addr) => *registers.get(&sf_addr)? as i16,
+        };
+        Some(self.decode(&words, sf))
+    }
+
+    /// Decodes the point from a block of registers starting at `start`, if the block covers
+    /// the point and its scale factor register.
+    pub(crate) fn decode_from(&self, start: Address, words: &[Word]) -> Option<f64> {
+        let (first, end) = self.span();
+        if first < start || end > u32::from(start) + words.len() as u32 {
+            return None;
+        }
+
+        let at = |addr: Address| usize::from(addr - start);
+        let sf = match self.scale_factor {
+            ScaleFactor::Static(sf) => sf,
+            ScaleFactor::Register(sf_addr) => words[at(sf_addr)] as i16,
+        };
+        let len = usize::from(self.data_type.word_count());
+        Some(self.decode(&words[at(self.addr)..at(self.addr) + len], sf))
+    }
+
+    /// Converts raw registers into the engineering value.
+    ///
+    /// Returns `NaN` if the scale factor is marked as not implemented.
+    pub fn decode(&self, words: &[Word], sf: i16) -> f64 {
+        if sf == SCALE_FACTOR_NOT_IMPLEMENTED {
+            return f64::NAN;
+        }
+        self.data_type.decode(words) * 10f64.powi(sf.into()) + self.offset
+    }
+
+    /// Converts an engineering value into raw registers, checking the range of the data type.
+    pub fn encode(&self, value: f64, sf: i16) -> io::Result<Vec<Word>> {
+        if sf == SCALE_FACTOR_NOT_IMPLEMENTED {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "scale factor not implemented",
+            ));
+        }
+        let raw = (value - self.offset) / 10f64.powi(sf.into());
+        match self.data_type {
+            DataType::F32 => self.data_type.encode(raw),
+            _ => self.data_type.encode(raw.round()),
+        }
+    }
+}
+
+impl RobustContext {
+    /// Reads all points and their scale factor registers with as few requests as
+    /// possible, without reading through larger gaps between them.
+    ///
+    /// Points whose registers the device rejects with `IllegalDataAddress` decode to
+    /// `NaN`, the other points are still read.
+    pub async fn read_points(&mut self, points: &[Point]) -> ModbusResult<Vec<f64>> {
+        let mut coalescer = ReadCoalescer::new(8, self.limits.read_registers);
+        let mut missing: Vec<Address> = points.iter().flat_map(Point::registers).collect();
+        let mut registers = BTreeMap::new();
+        while !missing.is_empty() {
+            let learned = coalescer.clone();
+            match self
+                .read_holding_registers_coalesced(&mut coalescer, &missing)
+                .await?
+            {
+                Ok(read) => registers.extend(read),
+                Err(e) => return Ok(Err(e)),
+            }
+            missing.retain(|addr| !registers.contains_key(addr));
+
+            // Rejected blocks are split and read again until only the offending registers
+            // are left.
+            let unchanged = learned.plan(RegisterTable::Holding, &missing);
+            let split = coalescer.plan(RegisterTable::Holding, &missing);
+            missing.retain(|addr| {
+                split
+                    .iter()
+                    .any(|block| block.contains(*addr) && !unchanged.contains(block))
+            });
+        }
+
+        Ok(Ok(points
+            .iter()
+            .map(|p| p.decode_registers(&registers).unwrap_or(f64::NAN))
+            .collect()))
+    }
+
+    /// Writes a point, reading its scale factor register first if necessary.
+    pub async fn write_point(&mut self, point: &Point, value: f64) -> ModbusResult<()> {
+        let sf = match point.scale_factor {
+            ScaleFactor::Static(sf) => sf,
+            ScaleFactor::Register(sf_addr) => {
+                match self.read_holding_registers(sf_addr, 1).await? {
+                    Ok(words) => words[0] as i16,
+                    Err(e) => return Ok(Err(e)),
+                }
+            }
+        };
+        let words = point.encode(value, sf).map_err(ModbusError::Transport)?;
+
+        match words.as_slice() {
+            [word] => self.write_single_register(point.addr, *word).await,
+            words => self.write_multiple_registers(point.addr, words).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+
+    #[test]
+    fn round_trip_with_scale_and_offset() {
+        let point = Point::new(0, DataType::I32)
+            .with_scale_factor(-2)
+            .with_offset(-40.0);
+        let words = point.encode(-12.34, -2).unwrap();
+        assert_eq!(words, vec![0, 2766]);
+        assert!((point.decode(&words, -2) - -12.34).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let point = Point::new(0, DataType::U16);
+        assert!(point.encode(-1.0, 0).is_err());
+        assert!(point.encode(65_536.0, 0).is_err());
+        assert!(point.encode(f64::NAN, 0).is_err());
+        assert!(point.encode(1.0, SCALE_FACTOR_NOT_IMPLEMENTED).is_err());
+        assert!(point.decode(&[1], SCALE_FACTOR_NOT_IMPLEMENTED).is_nan());
+    }
+
+    #[tokio::test]
+    async fn reads_points_with_scale_factor_registers() {
+        let mut image = RegisterImage::new();
+        // 2300 with a scale factor of -1, then a float and an unimplemented scale factor.
+        image.set(10, &[2300, (-1_i16) as Word]);
+        image.set(12, &[0x4049, 0x0fdb, 7, i16::MIN as Word]);
+        let ctx = context(image).await;
+
+        let points = [
+            Point::new(10, DataType::U16).with_scale_factor_register(11),
+            Point::new(12, DataType::F32),
+            Point::new(14, DataType::U16).with_scale_factor_register(15),
+        ];
+        let values = ctx.clone().read_points(&points).await.unwrap().unwrap();
+        assert_eq!(values[0], 230.0);
+        assert!((values[1] - std::f64::consts::PI).abs() < 1e-6);
+        assert!(values[2].is_nan());
+    }
+
+    #[tokio::test]
+    async fn reads_sparse_points_around_rejected_gaps() {
+        let mut image = RegisterImage::new();
+        image.set(0, &[1]);
+        image.set(5, &[2, (-1_i16) as Word]);
+        image.set(40_000, &[0, 3]);
+        let mut ctx = context(image).await;
+
+        let points = [
+            Point::new(0, DataType::U16),
+            Point::new(5, DataType::U16).with_scale_factor_register(6),
+            Point::new(40_000, DataType::U32),
+            Point::new(50_000, DataType::U16),
+        ];
+        let values = ctx.read_points(&points).await.unwrap().unwrap();
+        assert_eq!(values[..3], [1.0, 0.2, 3.0]);
+        assert!(values[3].is_nan());
+
+        // The block over the gap at 1..=4 was rejected once, then split.
+        let stats = ctx.stats().functions[&0x03];
+        assert_eq!((stats.successes, stats.failures), (3, 2));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    context::RobustContext,
+    types::{Coil, Word},
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use tokio_modbus::{prelude::*, Address, Error as ModbusError, Quantity};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PollRead {
+    Coils(Address, Quantity),
+    DiscreteInputs(Address, Quantity),
+    HoldingRegisters(Address, Quantity),
+    InputRegisters(Address, Quantity),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollValues {
+    Coils(Vec<Coil>),
+    Words(Vec<Word>),
+}
+
+#[derive(Debug, Clone)]
+pub enum PollError {
+    Exception(ExceptionCode),
+    Transport(Arc<ModbusError>),
+}
+
+/// Reads that are polled together at a fixed interval.
+#[derive(Debug, Clone)]
+pub struct PollGroup {
+    pub name: Arc<str>,
+    pub interval: Duration,
+    pub reads: Vec<PollRead>,
+}
+
+impl PollGroup {
+    pub fn new(name: &str, interval: Duration) -> Self {
+        Self {
+            name: name.into(),
+            interval,
+            reads: Vec::new(),
+        }
+    }
+
+    pub fn with_read(mut self, read: PollRead) -> Self {
+        self.reads.push(read);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PollSample {
+    pub group: Arc<str>,
+    pub read: PollRead,
+    pub timestamp: SystemTime,
+    pub result: Result<PollValues, PollError>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PollEvent {
+    Sample(PollSample),
+    /// A cycle of `group` took longer than its interval.
+    Overrun {
+        group: Arc<str>,
+        elapsed: Duration,
+        interval: Duration,
+    },
+}
+
+/// Polls groups of reads on a single robust connection.
+#[derive(Debug)]
+pub struct Poller {
+    ctx: RobustContext,
+    groups: Vec<PollGroup>,
+    sender: broadcast::Sender<PollEvent>,
+}
+
+impl Poller {
+    pub fn new(ctx: RobustContext) -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self {
+            ctx,
+            groups: Vec::new(),
+            sender,
+        }
+    }
+
+    pub fn with_group(mut self, group: PollGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PollEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Starts polling. Polling stops when the returned handle is stopped or dropped.
+    pub fn spawn(self) -> PollerHandle {
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let sender = self.sender.clone();
+        let task = tokio::spawn(self.run(stop_receiver));
+
+        PollerHandle {
+            sender,
+            stop_sender,
+            task,
+        }
+    }
+
+    async fn run(mut self, mut stop_receiver: oneshot::Receiver<()>) -> RobustContext {
+        let start = Instant::now();
+        let mut deadlines = vec![start; self.groups.len()];
+
+        while let Some((index, deadline)) = deadlines
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by_key(|(_, deadline)| *deadline)
+        {
+            tokio::select! {
+                _ = &mut stop_receiver => break,
+                _ = sleep_until(deadline) => {}
+            }
+
+            let group = self.groups[index].clone();
+            let cycle_start = Instant::now();
+            for read in &group.reads {
+                let sample = PollSample {
+                    group: group.name.clone(),
+                    read: *read,
+                    result: self.poll(*read).await,
+                    timestamp: SystemTime::now(),
+                };
+                let _ = self.sender.send(PollEvent::Sample(sample));
+            }
+
+            let elapsed = cycle_start.elapsed();
+            if elapsed > group.interval {
+                warn!(
+                    "poll group {} overran: took {:?}, interval {:?}",
+                    group.name, elapsed, group.interval
+                );
+                let _ = self.sender.send(PollEvent::Overrun {
+                    group: group.name.clone(),
+                    elapsed,
+                    interval: group.interval,
+                });
+            }
+            // Missed cycles are skipped rather than run back to back.
+            deadlines[index] = (deadline + group.interval).max(Instant::now());
+        }
+
+        self.ctx
+    }
+
+    async fn poll(&mut self, read: PollRead) -> Result<PollValues, PollError> {
+        let res = match read {
+            PollRead::Coils(addr, cnt) => self
+                .ctx
+                .read_coils(addr, cnt)
+                .await
+                .map(|res| res.map(PollValues::Coils)),
+            PollRead::DiscreteInputs(addr, cnt) => self
+                .ctx
+                .read_discrete_inputs(addr, cnt)
+                .await
+                .map(|res| res.map(PollValues::Coils)),
+            PollRead::HoldingRegisters(addr, cnt) => self
+                .ctx
+                .read_holding_registers(addr, cnt)
+                .await
+                .map(|res| res.map(PollValues::Words)),
+            PollRead::InputRegisters(addr, cnt) => self
+                .ctx
+                .read_input_registers(addr, cnt)
+                .await
+                .map(|res| res.map(PollValues::Words)),
+        };
+
+        match res {
+            Ok(Ok(values)) => Ok(values),
+            Ok(Err(e)) => Err(PollError::Exception(e)),
+            Err(e) => Err(PollError::Transport(Arc::new(e))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PollerHandle {
+    sender: broadcast::Sender<PollEvent>,
+    stop_sender: oneshot::Sender<()>,
+    task: JoinHandle<RobustContext>,
+}
+
+impl PollerHandle {
+    pub fn subscribe(&self) -> broadcast::Receiver<PollEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Stops polling after the running cycle and returns the context.
+    pub async fn stop(self) -> Result<RobustContext, tokio::task::JoinError> {
+        let _ = self.stop_sender.send(());
+        self.task.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{context, RegisterImage};
+    use tokio::sync::broadcast::error::TryRecvError;
+    use tokio::time::sleep;
+
+    async fn poller() -> Poller {
+        let mut image = RegisterImage::new();
+        image.set(0, &[1]);
+        let group = PollGroup::new("fast", Duration::from_secs(1))
+            .with_read(PollRead::HoldingRegisters(0, 1));
+        Poller::new(context(image).await).with_group(group)
+    }
+
+    /// Keeps the poller from reaching the device for `busy`.
+    async fn stall(ctx: &RobustContext, busy: Duration) {
+        let _guard = ctx.ctx.lock().await;
+        sleep(busy).await;
+    }
+
+    fn drain(events: &mut broadcast::Receiver<PollEvent>) -> Vec<PollEvent> {
+        let mut drained = Vec::new();
+        loop {
+            match events.try_recv() {
+                Ok(event) => drained.push(event),
+                Err(TryRecvError::Empty) => return drained,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    fn samples(events: &[PollEvent]) -> usize {
+        events
+            .iter()
+            .filter(|event| matches!(event, PollEvent::Sample(_)))
+            .count()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_on_interval() {
+        let poller = poller().await;
+        let mut events = poller.subscribe();
+        let handle = poller.spawn();
+
+        sleep(Duration::from_millis(2500)).await;
+        let events = drain(&mut events);
+        assert_eq!(samples(&events), 3);
+        let Some(PollEvent::Sample(sample)) = events.first() else {
+            panic!("{events:?}");
+        };
+        assert_eq!(sample.result.as_ref().unwrap(), &PollValues::Words(vec![1]));
+        handle.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_overrun() {
+        let poller = poller().await;
+        let ctx = poller.ctx.clone();
+        let mut events = poller.subscribe();
+        let stalled = stall(&ctx, Duration::from_millis(1500));
+        let handle = poller.spawn();
+
+        stalled.await;
+        sleep(Duration::from_millis(100)).await;
+        let overruns: Vec<_> = drain(&mut events)
+            .into_iter()
+            .filter_map(|event| match event {
+                PollEvent::Overrun {
+                    elapsed, interval, ..
+                } => Some((elapsed, interval)),
+                PollEvent::Sample(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            overruns,
+            vec![(Duration::from_millis(1500), Duration::from_secs(1))]
+        );
+        handle.stop().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_missed_deadlines() {
+        let poller = poller().await;
+        let ctx = poller.ctx.clone();
+        let mut events = poller.subscribe();
+        let stalled = stall(&ctx, Duration::from_millis(3500));
+        let handle = poller.spawn();
+
+        // The stalled cycle is followed by one catching up, not by one per missed
+        // deadline.
+        stalled.await;
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(samples(&drain(&mut events)), 2);
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(samples(&drain(&mut events)), 1);
+        handle.stop().await.unwrap();
+    }
+}
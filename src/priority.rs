@@ -0,0 +1,217 @@
+use std::collections::BinaryHeap;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// Order in which requests sharing a connection are served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Polling that may be cancelled while the link is degraded.
+    Background,
+    #[default]
+    Normal,
+    /// Control writes that go ahead of everything else.
+    Critical,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    wake: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        (self.priority, self.seq) == (other.priority, other.seq)
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    // Highest priority first, then first come first served.
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Debug, Default)]
+struct GateState {
+    busy: bool,
+    seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+/// Grants exclusive access to the connection, highest priority first.
+#[derive(Debug, Default)]
+pub(crate) struct PriorityGate {
+    state: Mutex<GateState>,
+}
+
+impl PriorityGate {
+    fn state(&self) -> std::sync::MutexGuard<'_, GateState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn acquire(&self, priority: Priority) -> GatePermit<'_> {
+        let receiver = {
+            let mut state = self.state();
+            if !state.busy {
+                state.busy = true;
+                return GatePermit { gate: self };
+            }
+
+            let (wake, receiver) = oneshot::channel();
+            state.seq += 1;
+            let seq = state.seq;
+            state.waiters.push(Waiter {
+                priority,
+                seq,
+                wake,
+            });
+            receiver
+        };
+
+        let mut waiting = Waiting {
+            gate: self,
+            receiver: Some(receiver),
+        };
+        if let Some(receiver) = waiting.receiver.as_mut() {
+            let _ = receiver.await;
+        }
+        waiting.receiver = None;
+
+        GatePermit { gate: self }
+    }
+
+    /// Hands the gate to the next waiter that is still interested.
+    fn release(&self) {
+        let mut state = self.state();
+        while let Some(waiter) = state.waiters.pop() {
+            if waiter.wake.send(()).is_ok() {
+                return;
+            }
+        }
+        state.busy = false;
+    }
+}
+
+/// Releases the gate if a waiter is dropped after it was handed the gate.
+struct Waiting<'a> {
+    gate: &'a PriorityGate,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if receiver.try_recv().is_ok() {
+                self.gate.release();
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct GatePermit<'a> {
+    gate: &'a PriorityGate,
+}
+
+impl Drop for GatePermit<'_> {
+    fn drop(&mut self) {
+        self.gate.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RobustError;
+    use crate::image::{context, RegisterImage};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::task::yield_now;
+    use tokio::time::timeout;
+    use tokio_modbus::prelude::*;
+
+    async fn wait_for_waiters(gate: &PriorityGate, n: usize) {
+        while gate.state().waiters.len() < n {
+            yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn critical_served_before_earlier_background() {
+        let gate = Arc::new(PriorityGate::default());
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let permit = gate.acquire(Priority::Normal).await;
+
+        let mut tasks = Vec::new();
+        for (n, priority) in [Priority::Background, Priority::Critical]
+            .into_iter()
+            .enumerate()
+        {
+            let (shared, served) = (gate.clone(), served.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = shared.acquire(priority).await;
+                served.lock().unwrap().push(priority);
+            }));
+            wait_for_waiters(&gate, n + 1).await;
+        }
+
+        drop(permit);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *served.lock().unwrap(),
+            vec![Priority::Critical, Priority::Background]
+        );
+    }
+
+    #[tokio::test]
+    async fn dropped_waiter_releases_handed_gate() {
+        let gate = PriorityGate::default();
+        let permit = gate.acquire(Priority::Normal).await;
+        let mut waiter = Box::pin(gate.acquire(Priority::Normal));
+        assert!(timeout(Duration::ZERO, &mut waiter).await.is_err());
+
+        // Hands the gate to the waiter, which is dropped before it runs.
+        drop(permit);
+        drop(waiter);
+        assert!(!gate.state().busy);
+        assert!(timeout(Duration::ZERO, gate.acquire(Priority::Background))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn background_cancelled_between_probes() {
+        let mut image = RegisterImage::new();
+        image.set(0, &[1]);
+        let ctx = context(image).await;
+        ctx.connected.store(false, Ordering::Relaxed);
+        let mut background = ctx.with_priority(Priority::Background);
+
+        // The first request probes the degraded link, the next one is shed.
+        assert!(background.read_holding_registers(0, 1).await.is_ok());
+        let err = background.read_holding_registers(0, 1).await.unwrap_err();
+        assert!(matches!(
+            RobustError::downcast(&err),
+            Some(RobustError::Cancelled)
+        ));
+
+        let mut normal = ctx.with_priority(Priority::Normal);
+        assert!(normal.read_holding_registers(0, 1).await.is_ok());
+    }
+}
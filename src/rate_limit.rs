@@ -0,0 +1,224 @@
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// Limits how fast requests, including retries, are sent to a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained requests per second, `None` for no limit.
+    pub max_rate: Option<f64>,
+    /// Requests that may be sent back to back before `max_rate` applies.
+    pub burst: u32,
+    /// Minimum time between the start of two requests.
+    pub min_spacing: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_rate: None,
+            burst: 1,
+            min_spacing: Duration::ZERO,
+        }
+    }
+}
+
+impl RateLimit {
+    /// # Panics
+    ///
+    /// If `max_rate` is not a positive, finite number.
+    pub fn with_max_rate(mut self, max_rate: f64, burst: u32) -> Self {
+        assert!(
+            max_rate.is_finite() && max_rate > 0.0,
+            "invalid max rate {max_rate}"
+        );
+        self.max_rate = Some(max_rate);
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn with_min_spacing(mut self, min_spacing: Duration) -> Self {
+        self.min_spacing = min_spacing;
+        self
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        if let Some(max_rate) = self.max_rate {
+            if !max_rate.is_finite() || max_rate <= 0.0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid max rate {max_rate}"),
+                ));
+            }
+        }
+        if self.burst == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "burst must allow at least one request",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+    last_request: Option<Instant>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        if let Some(max_rate) = self.limit.max_rate {
+            let elapsed = now.duration_since(self.refilled).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * max_rate).min(self.limit.burst.into());
+        }
+        self.refilled = now;
+    }
+
+    /// Takes a token, or returns how long to wait for the next one.
+    fn take(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.refill(now);
+
+        let spacing = self
+            .last_request
+            .map(|last| (last + self.limit.min_spacing).saturating_duration_since(now))
+            .unwrap_or_default();
+        let refill = match self.limit.max_rate {
+            Some(max_rate) if self.tokens < 1.0 => {
+                Duration::from_secs_f64((1.0 - self.tokens) / max_rate)
+            }
+            _ => Duration::ZERO,
+        };
+
+        let wait = spacing.max(refill);
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+
+        if self.limit.max_rate.is_some() {
+            self.tokens -= 1.0;
+        }
+        self.last_request = Some(now);
+        None
+    }
+}
+
+/// Token bucket shared by all clones of a context.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                limit: RateLimit::default(),
+                tokens: 1.0,
+                refilled: Instant::now(),
+                last_request: None,
+            }),
+        }
+    }
+}
+
+impl RateLimiter {
+    fn bucket(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.bucket().limit
+    }
+
+    /// Fails if `limit` has a rate that is not positive and finite, or a burst of 0.
+    pub fn set_limit(&self, limit: RateLimit) -> io::Result<()> {
+        limit.validate()?;
+        let mut bucket = self.bucket();
+        bucket.limit = limit;
+        bucket.tokens = limit.burst.into();
+        bucket.refilled = Instant::now();
+        Ok(())
+    }
+
+    /// Waits until the next request may be sent.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = self.bucket().take();
+            match wait {
+                Some(wait) => sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limit: RateLimit) -> RateLimiter {
+        let limiter = RateLimiter::default();
+        limiter.set_limit(limit).unwrap();
+        limiter
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_max_rate() {
+        let limiter = limiter(RateLimit::default().with_max_rate(10.0, 3));
+        for _ in 0..3 {
+            assert_eq!(limiter.bucket().take(), None);
+        }
+        let wait = limiter.bucket().take().unwrap();
+        assert!(wait <= Duration::from_millis(100) && wait > Duration::from_millis(90));
+
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_eq!(limiter.bucket().take(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn min_spacing() {
+        let limiter = limiter(RateLimit::default().with_min_spacing(Duration::from_millis(50)));
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unlimited_by_default() {
+        let limiter = RateLimiter::default();
+        for _ in 0..100 {
+            assert_eq!(limiter.bucket().take(), None);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        let limiter = RateLimiter::default();
+        for max_rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let limit = RateLimit {
+                max_rate: Some(max_rate),
+                ..Default::default()
+            };
+            assert!(limiter.set_limit(limit).is_err(), "{max_rate}");
+        }
+        let limit = RateLimit {
+            burst: 0,
+            ..Default::default()
+        };
+        assert!(limiter.set_limit(limit).is_err());
+        assert_eq!(limiter.limit(), RateLimit::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn with_max_rate_rejects_zero() {
+        let _ = RateLimit::default().with_max_rate(0.0, 1);
+    }
+}
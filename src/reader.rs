@@ -1,4 +1,5 @@
 use crate::{
+    chunk::chunks,
     context::RobustContext,
     try_read::{
         CoilsRead, DiscreteInputsRead, HoldingRegistersRead, InputRegistersRead,
@@ -6,12 +7,11 @@ use crate::{
     },
     types::{Coil, Word},
 };
-use tokio_modbus::{prelude::*, Address, Quantity, Result as ModbusResult};
-use tokio_retry::Retry;
+use tokio_modbus::{prelude::*, Address, FunctionCode, Quantity, Result as ModbusResult};
+use tracing::Instrument;
 
 impl Reader for RobustContext {
     #[doc = " Read multiple coils (0x01)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn read_coils<'life0, 'async_trait>(
         &'life0 mut self,
@@ -28,14 +28,27 @@ impl Reader for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { CoilsRead { addr, cnt }.try_read(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::ReadCoils, addr, cnt.into());
+        Box::pin(
+            async move {
+                let mut coils = Vec::with_capacity(cnt.into());
+                for (addr, cnt) in chunks(addr, cnt, self.limits.read_coils)? {
+                    let action = || async { CoilsRead { addr, cnt }.try_read(self).await };
+                    match self.retry(action).await? {
+                        Ok(chunk) => coils.extend(chunk),
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                if let Some(cache) = self.cache.get() {
+                    cache.store_coils(addr, &coils);
+                }
+                Ok(Ok(coils))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Read multiple discrete inputs (0x02)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn read_discrete_inputs<'life0, 'async_trait>(
         &'life0 mut self,
@@ -52,14 +65,27 @@ impl Reader for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { DiscreteInputsRead { addr, cnt }.try_read(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::ReadDiscreteInputs, addr, cnt.into());
+        Box::pin(
+            async move {
+                let mut coils = Vec::with_capacity(cnt.into());
+                for (addr, cnt) in chunks(addr, cnt, self.limits.read_coils)? {
+                    let action = || async { DiscreteInputsRead { addr, cnt }.try_read(self).await };
+                    match self.retry(action).await? {
+                        Ok(chunk) => coils.extend(chunk),
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                if let Some(cache) = self.cache.get() {
+                    cache.store_discrete_inputs(addr, &coils);
+                }
+                Ok(Ok(coils))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Read multiple holding registers (0x03)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn read_holding_registers<'life0, 'async_trait>(
         &'life0 mut self,
@@ -76,14 +102,28 @@ impl Reader for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { HoldingRegistersRead { addr, cnt }.try_read(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::ReadHoldingRegisters, addr, cnt.into());
+        Box::pin(
+            async move {
+                let mut words = Vec::with_capacity(cnt.into());
+                for (addr, cnt) in chunks(addr, cnt, self.limits.read_registers)? {
+                    let action =
+                        || async { HoldingRegistersRead { addr, cnt }.try_read(self).await };
+                    match self.retry(action).await? {
+                        Ok(chunk) => words.extend(chunk),
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                if let Some(cache) = self.cache.get() {
+                    cache.store_holding_registers(addr, &words);
+                }
+                Ok(Ok(words))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Read multiple input registers (0x04)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn read_input_registers<'life0, 'async_trait>(
         &'life0 mut self,
@@ -100,17 +140,30 @@ impl Reader for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { InputRegistersRead { addr, cnt }.try_read(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::ReadInputRegisters, addr, cnt.into());
+        Box::pin(
+            async move {
+                let mut words = Vec::with_capacity(cnt.into());
+                for (addr, cnt) in chunks(addr, cnt, self.limits.read_registers)? {
+                    let action = || async { InputRegistersRead { addr, cnt }.try_read(self).await };
+                    match self.retry(action).await? {
+                        Ok(chunk) => words.extend(chunk),
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                if let Some(cache) = self.cache.get() {
+                    cache.store_input_registers(addr, &words);
+                }
+                Ok(Ok(words))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Read and write multiple holding registers (0x17)"]
     #[doc = ""]
     #[doc = " The write operation is performed before the read unlike"]
     #[doc = " the name of the operation might suggest!"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn read_write_multiple_registers<'life0, 'life1, 'async_trait>(
         &'life0 mut self,
@@ -130,18 +183,31 @@ impl Reader for RobustContext {
         'life1: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async {
-                MultipleRegistersWriteRead {
-                    read_addr,
-                    read_count,
-                    write_addr,
-                    write_data,
+        let span = self.request_span(
+            FunctionCode::ReadWriteMultipleRegisters,
+            read_addr,
+            read_count.into(),
+        );
+        Box::pin(
+            async move {
+                let action = || async {
+                    MultipleRegistersWriteRead {
+                        read_addr,
+                        read_count,
+                        write_addr,
+                        write_data,
+                    }
+                    .try_read(self)
+                    .await
+                };
+                let res = self.retry(action).await;
+                if let (Some(cache), Ok(Ok(words))) = (self.cache.get(), &res) {
+                    cache.store_holding_registers(write_addr, write_data);
+                    cache.store_holding_registers(read_addr, words);
                 }
-                .try_read(self)
-                .await
-            };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+                res
+            }
+            .instrument(span),
+        )
     }
 }
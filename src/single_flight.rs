@@ -0,0 +1,191 @@
+use crate::error::RobustError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tokio_modbus::{
+    prelude::*, Address, Error as ModbusError, Quantity, Result as ModbusResult, SlaveId,
+};
+
+type SharedResult = Result<Result<Response, ExceptionCode>, RobustError>;
+
+/// Identifies reads that are answered by the same response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ReadKey {
+    slave: SlaveId,
+    function: u8,
+    addr: Address,
+    cnt: Quantity,
+}
+
+impl ReadKey {
+    /// Returns `None` for requests that must not be shared, i.e. everything but reads.
+    pub fn new(slave: Slave, request: &Request<'_>) -> Option<Self> {
+        let (addr, cnt) = match *request {
+            Request::ReadCoils(addr, cnt)
+            | Request::ReadDiscreteInputs(addr, cnt)
+            | Request::ReadHoldingRegisters(addr, cnt)
+            | Request::ReadInputRegisters(addr, cnt) => (addr, cnt),
+            _ => return None,
+        };
+
+        Some(Self {
+            slave: slave.0,
+            function: request.function_code().value(),
+            addr,
+            cnt,
+        })
+    }
+}
+
+/// Reads currently in flight, each waiting to fan out its result.
+#[derive(Debug, Default)]
+pub(crate) struct SingleFlight {
+    flights: Mutex<HashMap<ReadKey, broadcast::Sender<SharedResult>>>,
+}
+
+pub(crate) enum Flight<'a> {
+    /// Performs the read and shares its result through the guard.
+    Leader(FlightGuard<'a>),
+    /// Waits for the result of an identical read.
+    Follower(broadcast::Receiver<SharedResult>),
+}
+
+impl SingleFlight {
+    fn flights(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<ReadKey, broadcast::Sender<SharedResult>>> {
+        self.flights.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn join(&self, key: ReadKey) -> Flight<'_> {
+        let mut flights = self.flights();
+        if let Some(sender) = flights.get(&key) {
+            return Flight::Follower(sender.subscribe());
+        }
+
+        let (sender, _) = broadcast::channel(1);
+        flights.insert(key, sender.clone());
+        Flight::Leader(FlightGuard {
+            single_flight: self,
+            key,
+            sender,
+        })
+    }
+}
+
+/// Waits for the leader. Returns `None` if it was cancelled before finishing, in which
+/// case the follower performs the read itself.
+pub(crate) async fn wait(
+    receiver: &mut broadcast::Receiver<SharedResult>,
+) -> Option<ModbusResult<Response>> {
+    let shared = receiver.recv().await.ok()?;
+    Some(shared.map_err(ModbusError::from))
+}
+
+/// Removes the flight when dropped, so followers of a cancelled leader stop waiting.
+pub(crate) struct FlightGuard<'a> {
+    single_flight: &'a SingleFlight,
+    key: ReadKey,
+    sender: broadcast::Sender<SharedResult>,
+}
+
+impl FlightGuard<'_> {
+    fn remove(&self) {
+        let mut flights = self.single_flight.flights();
+        if flights
+            .get(&self.key)
+            .is_some_and(|sender| sender.same_channel(&self.sender))
+        {
+            flights.remove(&self.key);
+        }
+    }
+
+    /// Shares `res` with every follower and returns it to the leader.
+    pub fn complete(self, res: ModbusResult<Response>) -> ModbusResult<Response> {
+        self.remove();
+        if self.sender.receiver_count() == 0 {
+            return res;
+        }
+
+        let shared = res.map_err(RobustError::from);
+        let _ = self.sender.send(shared.clone());
+        shared.map_err(ModbusError::from)
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn key() -> ReadKey {
+        ReadKey::new(Slave(1), &Request::ReadHoldingRegisters(0, 2)).unwrap()
+    }
+
+    #[test]
+    fn only_reads_are_shared() {
+        assert!(ReadKey::new(Slave(1), &Request::WriteSingleRegister(0, 1)).is_none());
+        let coils = ReadKey::new(Slave(1), &Request::ReadCoils(0, 2)).unwrap();
+        assert_ne!(coils, key());
+    }
+
+    #[tokio::test]
+    async fn followers_share_result() {
+        let single_flight = SingleFlight::default();
+        let Flight::Leader(leader) = single_flight.join(key()) else {
+            panic!("first read must lead");
+        };
+        let Flight::Follower(mut follower) = single_flight.join(key()) else {
+            panic!("identical read must follow");
+        };
+
+        let res = leader.complete(Ok(Ok(Response::ReadHoldingRegisters(vec![1, 2]))));
+        assert!(matches!(res, Ok(Ok(Response::ReadHoldingRegisters(_)))));
+        let shared = wait(&mut follower).await.unwrap();
+        assert!(matches!(shared, Ok(Ok(Response::ReadHoldingRegisters(words))) if words == [1, 2]));
+        assert!(matches!(single_flight.join(key()), Flight::Leader(_)));
+    }
+
+    #[tokio::test]
+    async fn followers_keep_structured_error() {
+        let single_flight = SingleFlight::default();
+        let Flight::Leader(leader) = single_flight.join(key()) else {
+            panic!("first read must lead");
+        };
+        let Flight::Follower(mut follower) = single_flight.join(key()) else {
+            panic!("identical read must follow");
+        };
+
+        let e = RobustError::RetriesExhausted {
+            attempts: 4,
+            last: Box::new(RobustError::Timeout),
+        };
+        let res = leader.complete(Err(e.into()));
+        let shared = wait(&mut follower).await.unwrap();
+        for res in [res, shared] {
+            let e = res.unwrap_err();
+            assert!(matches!(
+                RobustError::downcast(&e),
+                Some(RobustError::RetriesExhausted { attempts: 4, .. })
+            ));
+            assert_eq!(RobustError::from(e).kind(), io::ErrorKind::TimedOut);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_leader_releases_followers() {
+        let single_flight = SingleFlight::default();
+        let leader = single_flight.join(key());
+        let Flight::Follower(mut follower) = single_flight.join(key()) else {
+            panic!("identical read must follow");
+        };
+        drop(leader);
+        assert!(wait(&mut follower).await.is_none());
+    }
+}
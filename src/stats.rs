@@ -0,0 +1,244 @@
+use crate::context::RobustContext;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio_modbus::{prelude::*, FunctionCode, Result as ModbusResult};
+
+/// Latencies kept for the percentile.
+const LATENCY_WINDOW: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FunctionStats {
+    pub successes: u64,
+    /// Exceptions and transport errors.
+    pub failures: u64,
+}
+
+/// Of requests that were sent; requests failing for lack of a connection are not timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyStats {
+    pub avg: Duration,
+    /// Over the last 1024 requests.
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastError {
+    pub timestamp: SystemTime,
+    pub message: String,
+}
+
+/// Snapshot of the statistics of a [`RobustContext`] and its clones.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Time since the current connection was established, `None` while disconnected.
+    pub uptime: Option<Duration>,
+    /// Successful connects after the first one.
+    pub reconnects: u64,
+    pub last_error: Option<LastError>,
+    /// Keyed by function code.
+    pub functions: BTreeMap<u8, FunctionStats>,
+    /// Number of exception responses, keyed by exception code.
+    pub exceptions: BTreeMap<u8, u64>,
+    pub latency: LatencyStats,
+}
+
+#[derive(Debug, Default)]
+struct Collector {
+    connected_since: Option<Instant>,
+    connects: u64,
+    last_error: Option<LastError>,
+    functions: BTreeMap<u8, FunctionStats>,
+    exceptions: BTreeMap<u8, u64>,
+    sent: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+    latencies: VecDeque<Duration>,
+}
+
+impl Collector {
+    fn error(&mut self, message: String) {
+        self.last_error = Some(LastError {
+            timestamp: SystemTime::now(),
+            message,
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StatsCollector {
+    collector: Mutex<Collector>,
+}
+
+impl StatsCollector {
+    fn collector(&self) -> std::sync::MutexGuard<'_, Collector> {
+        self.collector.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// `latency` is `None` if the request failed without being sent.
+    pub fn record_request(
+        &self,
+        function: FunctionCode,
+        res: &ModbusResult<Response>,
+        latency: Option<Duration>,
+    ) {
+        let mut collector = self.collector();
+        let function = collector.functions.entry(function.value()).or_default();
+        match res {
+            Ok(Ok(_)) => function.successes += 1,
+            Ok(Err(e)) => {
+                function.failures += 1;
+                *collector.exceptions.entry((*e).into()).or_default() += 1;
+            }
+            Err(_) => function.failures += 1,
+        }
+        match res {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => collector.error(e.to_string()),
+            Err(e) => collector.error(e.to_string()),
+        }
+
+        let Some(latency) = latency else {
+            return;
+        };
+        collector.sent += 1;
+        collector.total_latency = collector.total_latency.saturating_add(latency);
+        collector.max_latency = collector.max_latency.max(latency);
+        if collector.latencies.len() == LATENCY_WINDOW {
+            collector.latencies.pop_front();
+        }
+        collector.latencies.push_back(latency);
+    }
+
+    pub fn record_connect(&self, res: Result<(), &std::io::Error>) {
+        let mut collector = self.collector();
+        match res {
+            Ok(()) => {
+                collector.connected_since = Some(Instant::now());
+                collector.connects += 1;
+            }
+            Err(e) => {
+                collector.connected_since = None;
+                collector.error(format!("could not connect: {}", e));
+            }
+        }
+    }
+
+    pub fn record_disconnect(&self) {
+        self.collector().connected_since = None;
+    }
+
+    fn snapshot(&self) -> Stats {
+        let collector = self.collector();
+
+        let mut latencies = collector.latencies.iter().copied().collect::<Vec<_>>();
+        latencies.sort_unstable();
+        let p95 = latencies
+            .get((latencies.len() * 95).div_ceil(100).saturating_sub(1))
+            .copied()
+            .unwrap_or_default();
+        let avg = collector
+            .total_latency
+            .as_nanos()
+            .checked_div(collector.sent.into())
+            .map(|nanos| Duration::from_nanos(nanos as u64))
+            .unwrap_or_default();
+
+        Stats {
+            uptime: collector.connected_since.map(|since| since.elapsed()),
+            reconnects: collector.connects.saturating_sub(1),
+            last_error: collector.last_error.clone(),
+            functions: collector.functions.clone(),
+            exceptions: collector.exceptions.clone(),
+            latency: LatencyStats {
+                avg,
+                p95,
+                max: collector.max_latency,
+            },
+        }
+    }
+}
+
+impl RobustContext {
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RobustError;
+    use std::io;
+
+    fn ok() -> ModbusResult<Response> {
+        Ok(Ok(Response::ReadHoldingRegisters(vec![0])))
+    }
+
+    fn ms(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn counts_outcomes_per_function() {
+        let stats = StatsCollector::default();
+        let read = FunctionCode::ReadHoldingRegisters;
+        stats.record_request(read, &ok(), ms(1));
+        stats.record_request(read, &Ok(Err(ExceptionCode::IllegalDataAddress)), ms(1));
+        let e = RobustError::Timeout.into();
+        stats.record_request(FunctionCode::WriteSingleCoil, &Err(e), ms(1));
+
+        let snapshot = stats.snapshot();
+        let read = snapshot.functions[&0x03];
+        assert_eq!((read.successes, read.failures), (1, 1));
+        assert_eq!(snapshot.functions[&0x05].failures, 1);
+        assert_eq!(snapshot.exceptions[&0x02], 1);
+        let last_error = snapshot.last_error.unwrap();
+        assert!(
+            last_error.message.contains("timed out"),
+            "{}",
+            last_error.message
+        );
+    }
+
+    #[test]
+    fn latency_skips_unsent_requests() {
+        let stats = StatsCollector::default();
+        let read = FunctionCode::ReadHoldingRegisters;
+        for latency in 1..=100 {
+            stats.record_request(read, &ok(), ms(latency));
+        }
+        let e = io::Error::from(io::ErrorKind::ConnectionRefused);
+        for _ in 0..100 {
+            let res = Err(RobustError::connect_failed(&e).into());
+            stats.record_request(read, &res, None);
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.functions[&0x03].failures, 100);
+        assert_eq!(snapshot.latency.avg, Duration::from_micros(50_500));
+        assert_eq!(snapshot.latency.p95, Duration::from_millis(95));
+        assert_eq!(snapshot.latency.max, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn uptime_and_reconnects() {
+        let stats = StatsCollector::default();
+        assert_eq!(stats.snapshot().uptime, None);
+        stats.record_connect(Ok(()));
+        assert!(stats.snapshot().uptime.is_some());
+        stats.record_disconnect();
+        assert_eq!(stats.snapshot().uptime, None);
+        stats.record_connect(Err(&io::ErrorKind::ConnectionRefused.into()));
+        stats.record_connect(Ok(()));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.reconnects, 1);
+        assert!(snapshot
+            .last_error
+            .unwrap()
+            .message
+            .starts_with("could not connect"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{
+    codec::{decode_string, ByteOrder},
+    types::Word,
+};
+
+/// Register data of a single model, indexed by offset from the model start.
+///
+/// All accessors return `None` for values marked as not implemented and for offsets
+/// beyond the model length.
+pub(crate) struct Block<'a> {
+    words: &'a [Word],
+}
+
+impl<'a> Block<'a> {
+    pub fn new(words: &'a [Word]) -> Self {
+        Self { words }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn word(&self, off: usize) -> Option<Word> {
+        self.words.get(off).copied()
+    }
+
+    fn dword(&self, off: usize) -> Option<u32> {
+        Some((u32::from(self.word(off)?) << 16) | u32::from(self.word(off + 1)?))
+    }
+
+    fn qword(&self, off: usize) -> Option<u64> {
+        Some((u64::from(self.dword(off)?) << 32) | u64::from(self.dword(off + 2)?))
+    }
+
+    pub fn u16(&self, off: usize) -> Option<u16> {
+        self.word(off).filter(|w| *w != 0xffff)
+    }
+
+    pub fn i16(&self, off: usize) -> Option<i16> {
+        self.word(off).map(|w| w as i16).filter(|w| *w != i16::MIN)
+    }
+
+    pub fn u32(&self, off: usize) -> Option<u32> {
+        self.dword(off).filter(|w| *w != 0xffff_ffff)
+    }
+
+    pub fn acc32(&self, off: usize) -> Option<u32> {
+        self.dword(off).filter(|w| *w != 0)
+    }
+
+    pub fn acc64(&self, off: usize) -> Option<u64> {
+        self.qword(off).filter(|w| *w != 0)
+    }
+
+    pub fn f32(&self, off: usize) -> Option<f32> {
+        self.dword(off).map(f32::from_bits).filter(|f| !f.is_nan())
+    }
+
+    pub fn string(&self, off: usize, len: usize) -> String {
+        let end = self.words.len().min(off + len);
+        decode_string(
+            self.words.get(off..end).unwrap_or_default(),
+            ByteOrder::BigEndian,
+        )
+    }
+
+    fn sf(&self, off: usize) -> Option<i32> {
+        self.i16(off).map(i32::from)
+    }
+
+    pub fn scaled_u16(&self, off: usize, sf_off: usize) -> Option<f64> {
+        Some(f64::from(self.u16(off)?) * 10f64.powi(self.sf(sf_off)?))
+    }
+
+    pub fn scaled_i16(&self, off: usize, sf_off: usize) -> Option<f64> {
+        Some(f64::from(self.i16(off)?) * 10f64.powi(self.sf(sf_off)?))
+    }
+
+    pub fn scaled_acc32(&self, off: usize, sf_off: usize) -> Option<f64> {
+        Some(f64::from(self.acc32(off)?) * 10f64.powi(self.sf(sf_off)?))
+    }
+
+    pub fn float(&self, off: usize) -> Option<f64> {
+        self.f32(off).map(f64::from)
+    }
+}
@@ -0,0 +1,172 @@
+mod block;
+mod models;
+
+pub use models::{
+    Common, Controls, Inverter, InverterState, Meter, Model, Mppt, MpptModule, Nameplate, Settings,
+    Status, Storage,
+};
+
+use tokio_modbus::{prelude::*, Address, ExceptionCode, Result as ModbusResult};
+
+/// Addresses probed for the "SunS" marker, in order.
+pub const BASE_ADDRESSES: [Address; 3] = [40000, 50000, 0];
+
+const MARKER: [u16; 2] = [0x5375, 0x6e53];
+const END_MODEL_ID: u16 = 0xffff;
+
+/// Location of a model in the register map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelHeader {
+    pub id: u16,
+    /// Address of the first register after the model id and length.
+    pub addr: Address,
+    pub len: u16,
+}
+
+/// Scans the base addresses for the SunSpec marker and walks the model chain.
+///
+/// Returns an empty list if no base address carries the marker.
+pub async fn discover<R: Reader + ?Sized>(reader: &mut R) -> ModbusResult<Vec<ModelHeader>> {
+    for base in BASE_ADDRESSES {
+        match reader.read_holding_registers(base, 2).await? {
+            Ok(words) if words == MARKER => return walk(reader, base + 2).await,
+            Ok(_) | Err(ExceptionCode::IllegalDataAddress) => continue,
+            Err(e) => return Ok(Err(e)),
+        }
+    }
+
+    Ok(Ok(Vec::new()))
+}
+
+async fn walk<R: Reader + ?Sized>(
+    reader: &mut R,
+    mut addr: Address,
+) -> ModbusResult<Vec<ModelHeader>> {
+    let mut headers = Vec::new();
+    loop {
+        let header = match reader.read_holding_registers(addr, 2).await? {
+            Ok(words) => words,
+            Err(e) => return Ok(Err(e)),
+        };
+        let (id, len) = (header[0], header[1]);
+        if id == END_MODEL_ID {
+            break;
+        }
+
+        let Some(data_addr) = addr.checked_add(2) else {
+            break;
+        };
+        headers.push(ModelHeader {
+            id,
+            addr: data_addr,
+            len,
+        });
+        match data_addr.checked_add(len) {
+            Some(next) => addr = next,
+            None => break,
+        }
+    }
+
+    Ok(Ok(headers))
+}
+
+/// Reads and decodes a single model.
+pub async fn read_model<R: Reader + ?Sized>(
+    reader: &mut R,
+    header: ModelHeader,
+) -> ModbusResult<Model> {
+    reader
+        .read_holding_registers(header.addr, header.len)
+        .await
+        .map(|res| res.map(|words| Model::decode(header.id, &words)))
+}
+
+/// Discovers and decodes all models of a device.
+pub async fn scan<R: Reader + ?Sized>(reader: &mut R) -> ModbusResult<Vec<Model>> {
+    let headers = match discover(reader).await? {
+        Ok(headers) => headers,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let mut models = Vec::with_capacity(headers.len());
+    for header in headers {
+        match read_model(reader, header).await? {
+            Ok(model) => models.push(model),
+            Err(e) => return Ok(Err(e)),
+        }
+    }
+
+    Ok(Ok(models))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::{encode_string, ByteOrder, Padding},
+        image::RegisterImage,
+    };
+
+    fn device() -> client::Context {
+        let mut image = RegisterImage::new();
+        image.set(40000, &MARKER);
+
+        let mut common = vec![1, 66];
+        for (s, cnt) in [("Acme", 16), ("X1", 16), ("", 8), ("1.0", 8), ("SN42", 16)] {
+            common.extend(encode_string(s, cnt, ByteOrder::BigEndian, Padding::Nul).unwrap());
+        }
+        common.extend([7, 0]);
+        image.set(40002, &common);
+
+        let mut inverter = vec![103, 50];
+        inverter.extend([0xffff; 50]);
+        // 230.0 V on phase A with a scale factor of -1.
+        inverter[2 + 8] = 2300;
+        inverter[2 + 11] = (-1_i16) as u16;
+        inverter[2 + 36] = 4;
+        image.set(40070, &inverter);
+
+        image.set(40122, &[END_MODEL_ID, 0]);
+        client::Context::from(image)
+    }
+
+    #[tokio::test]
+    async fn discovers_model_chain() {
+        let headers = discover(&mut device()).await.unwrap().unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                ModelHeader {
+                    id: 1,
+                    addr: 40004,
+                    len: 66,
+                },
+                ModelHeader {
+                    id: 103,
+                    addr: 40072,
+                    len: 50,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_models() {
+        let models = scan(&mut device()).await.unwrap().unwrap();
+        let [Model::Common(common), Model::Inverter(inverter)] = &models[..] else {
+            panic!("unexpected models {:?}", models);
+        };
+        assert_eq!(common.manufacturer, "Acme");
+        assert_eq!(common.serial_number, "SN42");
+        assert_eq!(common.device_address, Some(7));
+        assert_eq!(inverter.phase_voltage[0], Some(230.0));
+        assert_eq!(inverter.phase_voltage[1], None);
+        assert_eq!(inverter.state, Some(InverterState::Mppt));
+    }
+
+    #[tokio::test]
+    async fn no_marker_finds_nothing() {
+        let mut image = client::Context::from(RegisterImage::new());
+        assert_eq!(discover(&mut image).await.unwrap().unwrap(), vec![]);
+    }
+}
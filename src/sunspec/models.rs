@@ -0,0 +1,627 @@
+use super::block::Block;
+use crate::{bitfield::RegisterEnum, types::Word};
+
+/// A decoded SunSpec model with scale factors applied.
+///
+/// Values the device marks as not implemented are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    /// Model 1
+    Common(Common),
+    /// Models 101-103 (integer with scale factors) and 111-113 (float)
+    Inverter(Inverter),
+    /// Model 120
+    Nameplate(Nameplate),
+    /// Model 121
+    Settings(Settings),
+    /// Model 122
+    Status(Status),
+    /// Model 123
+    Controls(Controls),
+    /// Model 124
+    Storage(Storage),
+    /// Model 160
+    Mppt(Mppt),
+    /// Models 201-204
+    Meter(Box<Meter>),
+    Unknown {
+        id: u16,
+        words: Vec<Word>,
+    },
+}
+
+impl Model {
+    pub fn decode(id: u16, words: &[Word]) -> Self {
+        let block = Block::new(words);
+        match id {
+            1 => Model::Common(Common::decode(&block)),
+            101..=103 => Model::Inverter(Inverter::decode(id, &block)),
+            111..=113 => Model::Inverter(Inverter::decode_float(id, &block)),
+            120 => Model::Nameplate(Nameplate::decode(&block)),
+            121 => Model::Settings(Settings::decode(&block)),
+            122 => Model::Status(Status::decode(&block)),
+            123 => Model::Controls(Controls::decode(&block)),
+            124 => Model::Storage(Storage::decode(&block)),
+            160 => Model::Mppt(Mppt::decode(&block)),
+            201..=204 => Model::Meter(Box::new(Meter::decode(id, &block))),
+            _ => Model::Unknown {
+                id,
+                words: words.to_vec(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Common {
+    pub manufacturer: String,
+    pub model: String,
+    pub options: String,
+    pub version: String,
+    pub serial_number: String,
+    pub device_address: Option<u16>,
+}
+
+impl Common {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            manufacturer: b.string(0, 16),
+            model: b.string(16, 16),
+            options: b.string(32, 8),
+            version: b.string(40, 8),
+            serial_number: b.string(48, 16),
+            device_address: b.u16(64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InverterState {
+    Off,
+    Sleeping,
+    Starting,
+    Mppt,
+    Throttled,
+    ShuttingDown,
+    Fault,
+    Standby,
+    Other(u16),
+}
+
+impl From<u16> for InverterState {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => InverterState::Off,
+            2 => InverterState::Sleeping,
+            3 => InverterState::Starting,
+            4 => InverterState::Mppt,
+            5 => InverterState::Throttled,
+            6 => InverterState::ShuttingDown,
+            7 => InverterState::Fault,
+            8 => InverterState::Standby,
+            other => InverterState::Other(other),
+        }
+    }
+}
+
+impl RegisterEnum for InverterState {
+    fn from_word(word: Word) -> Option<Self> {
+        (word != 0xffff).then(|| InverterState::from(word))
+    }
+
+    fn to_word(&self) -> Word {
+        match self {
+            InverterState::Off => 1,
+            InverterState::Sleeping => 2,
+            InverterState::Starting => 3,
+            InverterState::Mppt => 4,
+            InverterState::Throttled => 5,
+            InverterState::ShuttingDown => 6,
+            InverterState::Fault => 7,
+            InverterState::Standby => 8,
+            InverterState::Other(other) => *other,
+        }
+    }
+}
+
+/// Phase values are ordered A, B, C.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inverter {
+    pub id: u16,
+    /// A
+    pub current: Option<f64>,
+    /// A
+    pub phase_current: [Option<f64>; 3],
+    /// V, ordered AB, BC, CA
+    pub line_voltage: [Option<f64>; 3],
+    /// V
+    pub phase_voltage: [Option<f64>; 3],
+    /// W
+    pub power: Option<f64>,
+    /// Hz
+    pub frequency: Option<f64>,
+    /// VA
+    pub apparent_power: Option<f64>,
+    /// var
+    pub reactive_power: Option<f64>,
+    /// Percent
+    pub power_factor: Option<f64>,
+    /// Wh
+    pub energy: Option<f64>,
+    /// A
+    pub dc_current: Option<f64>,
+    /// V
+    pub dc_voltage: Option<f64>,
+    /// W
+    pub dc_power: Option<f64>,
+    /// °C
+    pub cabinet_temperature: Option<f64>,
+    /// °C
+    pub heat_sink_temperature: Option<f64>,
+    /// °C
+    pub transformer_temperature: Option<f64>,
+    /// °C
+    pub other_temperature: Option<f64>,
+    pub state: Option<InverterState>,
+    pub vendor_state: Option<u16>,
+    pub events: Option<u32>,
+}
+
+impl Inverter {
+    fn decode(id: u16, b: &Block<'_>) -> Self {
+        Self {
+            id,
+            current: b.scaled_u16(0, 4),
+            phase_current: [1, 2, 3].map(|off| b.scaled_u16(off, 4)),
+            line_voltage: [5, 6, 7].map(|off| b.scaled_u16(off, 11)),
+            phase_voltage: [8, 9, 10].map(|off| b.scaled_u16(off, 11)),
+            power: b.scaled_i16(12, 13),
+            frequency: b.scaled_u16(14, 15),
+            apparent_power: b.scaled_i16(16, 17),
+            reactive_power: b.scaled_i16(18, 19),
+            power_factor: b.scaled_i16(20, 21),
+            energy: b.scaled_acc32(22, 24),
+            dc_current: b.scaled_u16(25, 26),
+            dc_voltage: b.scaled_u16(27, 28),
+            dc_power: b.scaled_i16(29, 30),
+            cabinet_temperature: b.scaled_i16(31, 35),
+            heat_sink_temperature: b.scaled_i16(32, 35),
+            transformer_temperature: b.scaled_i16(33, 35),
+            other_temperature: b.scaled_i16(34, 35),
+            state: b.u16(36).map(InverterState::from),
+            vendor_state: b.u16(37),
+            events: b.u32(38),
+        }
+    }
+
+    fn decode_float(id: u16, b: &Block<'_>) -> Self {
+        Self {
+            id,
+            current: b.float(0),
+            phase_current: [2, 4, 6].map(|off| b.float(off)),
+            line_voltage: [8, 10, 12].map(|off| b.float(off)),
+            phase_voltage: [14, 16, 18].map(|off| b.float(off)),
+            power: b.float(20),
+            frequency: b.float(22),
+            apparent_power: b.float(24),
+            reactive_power: b.float(26),
+            power_factor: b.float(28),
+            energy: b.float(30),
+            dc_current: b.float(32),
+            dc_voltage: b.float(34),
+            dc_power: b.float(36),
+            cabinet_temperature: b.float(38),
+            heat_sink_temperature: b.float(40),
+            transformer_temperature: b.float(42),
+            other_temperature: b.float(44),
+            state: b.u16(46).map(InverterState::from),
+            vendor_state: b.u16(47),
+            events: b.u32(48),
+        }
+    }
+}
+
+/// Quadrant values are ordered Q1 to Q4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nameplate {
+    pub der_type: Option<u16>,
+    /// W
+    pub power_rating: Option<f64>,
+    /// VA
+    pub apparent_power_rating: Option<f64>,
+    /// var
+    pub reactive_power_rating: [Option<f64>; 4],
+    /// A
+    pub current_rating: Option<f64>,
+    /// cos()
+    pub power_factor_rating: [Option<f64>; 4],
+    /// Wh
+    pub energy_rating: Option<f64>,
+    /// Ah
+    pub capacity_rating: Option<f64>,
+    /// W
+    pub max_charge_rate: Option<f64>,
+    /// W
+    pub max_discharge_rate: Option<f64>,
+}
+
+impl Nameplate {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            der_type: b.u16(0),
+            power_rating: b.scaled_u16(1, 2),
+            apparent_power_rating: b.scaled_u16(3, 4),
+            reactive_power_rating: [5, 6, 7, 8].map(|off| b.scaled_i16(off, 9)),
+            current_rating: b.scaled_u16(10, 11),
+            power_factor_rating: [12, 13, 14, 15].map(|off| b.scaled_i16(off, 16)),
+            energy_rating: b.scaled_u16(17, 18),
+            capacity_rating: b.scaled_u16(19, 20),
+            max_charge_rate: b.scaled_u16(21, 22),
+            max_discharge_rate: b.scaled_u16(23, 24),
+        }
+    }
+}
+
+/// Quadrant values are ordered Q1 to Q4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// W
+    pub max_power: Option<f64>,
+    /// V
+    pub voltage_reference: Option<f64>,
+    /// V
+    pub voltage_reference_offset: Option<f64>,
+    /// V
+    pub max_voltage: Option<f64>,
+    /// V
+    pub min_voltage: Option<f64>,
+    /// VA
+    pub max_apparent_power: Option<f64>,
+    /// var
+    pub max_reactive_power: [Option<f64>; 4],
+    /// % WMax/s
+    pub ramp_rate: Option<f64>,
+    /// cos()
+    pub min_power_factor: [Option<f64>; 4],
+    pub var_action: Option<u16>,
+    pub apparent_power_calculation: Option<u16>,
+    /// % ramp rate
+    pub max_ramp_rate: Option<f64>,
+    /// Hz
+    pub nominal_frequency: Option<f64>,
+    pub connected_phase: Option<u16>,
+}
+
+impl Settings {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            max_power: b.scaled_u16(0, 20),
+            voltage_reference: b.scaled_u16(1, 21),
+            voltage_reference_offset: b.scaled_i16(2, 22),
+            max_voltage: b.scaled_u16(3, 23),
+            min_voltage: b.scaled_u16(4, 23),
+            max_apparent_power: b.scaled_u16(5, 24),
+            max_reactive_power: [6, 7, 8, 9].map(|off| b.scaled_i16(off, 25)),
+            ramp_rate: b.scaled_u16(10, 26),
+            min_power_factor: [11, 12, 13, 14].map(|off| b.scaled_i16(off, 27)),
+            var_action: b.u16(15),
+            apparent_power_calculation: b.u16(16),
+            max_ramp_rate: b.scaled_u16(17, 28),
+            nominal_frequency: b.scaled_u16(18, 29),
+            connected_phase: b.u16(19),
+        }
+    }
+}
+
+/// Quadrant values are ordered Q1 to Q4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub pv_connection: Option<u16>,
+    pub storage_connection: Option<u16>,
+    pub ecp_connection: Option<u16>,
+    /// Wh
+    pub active_energy: Option<u64>,
+    /// VAh
+    pub apparent_energy: Option<u64>,
+    /// varh
+    pub reactive_energy: [Option<u64>; 4],
+    /// var
+    pub available_reactive_power: Option<f64>,
+    /// W
+    pub available_power: Option<f64>,
+    pub set_limit_mask: Option<u32>,
+    pub active_controls: Option<u32>,
+    pub time_source: String,
+    /// Seconds since 2000-01-01 00:00 UTC
+    pub timestamp: Option<u32>,
+    pub ride_through_status: Option<u16>,
+    /// Ohm
+    pub isolation_resistance: Option<f64>,
+}
+
+impl Status {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            pv_connection: b.u16(0),
+            storage_connection: b.u16(1),
+            ecp_connection: b.u16(2),
+            active_energy: b.acc64(3),
+            apparent_energy: b.acc64(7),
+            reactive_energy: [11, 15, 19, 23].map(|off| b.acc64(off)),
+            available_reactive_power: b.scaled_u16(27, 28),
+            available_power: b.scaled_u16(29, 30),
+            set_limit_mask: b.u32(31),
+            active_controls: b.u32(33),
+            time_source: b.string(35, 4),
+            timestamp: b.u32(39),
+            ride_through_status: b.u16(41),
+            isolation_resistance: b.scaled_u16(42, 43),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Controls {
+    /// Seconds
+    pub connection_window: Option<u16>,
+    /// Seconds
+    pub connection_revert_timeout: Option<u16>,
+    pub connection: Option<u16>,
+    /// % WMax
+    pub power_limit: Option<f64>,
+    /// Seconds
+    pub power_limit_window: Option<u16>,
+    /// Seconds
+    pub power_limit_revert_timeout: Option<u16>,
+    /// Seconds
+    pub power_limit_ramp_time: Option<u16>,
+    pub power_limit_enabled: Option<u16>,
+    /// cos()
+    pub power_factor: Option<f64>,
+    /// Seconds
+    pub power_factor_window: Option<u16>,
+    /// Seconds
+    pub power_factor_revert_timeout: Option<u16>,
+    /// Seconds
+    pub power_factor_ramp_time: Option<u16>,
+    pub power_factor_enabled: Option<u16>,
+    /// % WMax
+    pub reactive_power_of_max_power: Option<f64>,
+    /// % VArMax
+    pub reactive_power_of_max_reactive_power: Option<f64>,
+    /// % VArAval
+    pub reactive_power_of_available: Option<f64>,
+    /// Seconds
+    pub reactive_power_window: Option<u16>,
+    /// Seconds
+    pub reactive_power_revert_timeout: Option<u16>,
+    /// Seconds
+    pub reactive_power_ramp_time: Option<u16>,
+    pub reactive_power_mode: Option<u16>,
+    pub reactive_power_enabled: Option<u16>,
+}
+
+impl Controls {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            connection_window: b.u16(0),
+            connection_revert_timeout: b.u16(1),
+            connection: b.u16(2),
+            power_limit: b.scaled_u16(3, 21),
+            power_limit_window: b.u16(4),
+            power_limit_revert_timeout: b.u16(5),
+            power_limit_ramp_time: b.u16(6),
+            power_limit_enabled: b.u16(7),
+            power_factor: b.scaled_i16(8, 22),
+            power_factor_window: b.u16(9),
+            power_factor_revert_timeout: b.u16(10),
+            power_factor_ramp_time: b.u16(11),
+            power_factor_enabled: b.u16(12),
+            reactive_power_of_max_power: b.scaled_i16(13, 23),
+            reactive_power_of_max_reactive_power: b.scaled_i16(14, 23),
+            reactive_power_of_available: b.scaled_i16(15, 23),
+            reactive_power_window: b.u16(16),
+            reactive_power_revert_timeout: b.u16(17),
+            reactive_power_ramp_time: b.u16(18),
+            reactive_power_mode: b.u16(19),
+            reactive_power_enabled: b.u16(20),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Storage {
+    /// W
+    pub max_charge_power: Option<f64>,
+    /// % WChaMax/s
+    pub charge_gradient: Option<f64>,
+    /// % WChaMax/s
+    pub discharge_gradient: Option<f64>,
+    pub control_mode: Option<u16>,
+    /// VA
+    pub max_charge_apparent_power: Option<f64>,
+    /// % WChaMax
+    pub min_reserve: Option<f64>,
+    /// % AhrRtg
+    pub state_of_charge: Option<f64>,
+    /// AH
+    pub available_energy: Option<f64>,
+    /// V
+    pub battery_voltage: Option<f64>,
+    pub charge_status: Option<u16>,
+    /// % WDisChaMax
+    pub discharge_rate: Option<f64>,
+    /// % WChaMax
+    pub charge_rate: Option<f64>,
+    /// Seconds
+    pub rate_window: Option<u16>,
+    /// Seconds
+    pub rate_revert_timeout: Option<u16>,
+    /// Seconds
+    pub rate_ramp_time: Option<u16>,
+    pub grid_charging: Option<u16>,
+}
+
+impl Storage {
+    fn decode(b: &Block<'_>) -> Self {
+        Self {
+            max_charge_power: b.scaled_u16(0, 16),
+            charge_gradient: b.scaled_u16(1, 17),
+            discharge_gradient: b.scaled_u16(2, 17),
+            control_mode: b.u16(3),
+            max_charge_apparent_power: b.scaled_u16(4, 18),
+            min_reserve: b.scaled_u16(5, 19),
+            state_of_charge: b.scaled_u16(6, 20),
+            available_energy: b.scaled_u16(7, 21),
+            battery_voltage: b.scaled_u16(8, 22),
+            charge_status: b.u16(9),
+            discharge_rate: b.scaled_i16(10, 23),
+            charge_rate: b.scaled_i16(11, 23),
+            rate_window: b.u16(12),
+            rate_revert_timeout: b.u16(13),
+            rate_ramp_time: b.u16(14),
+            grid_charging: b.u16(15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mppt {
+    pub events: Option<u32>,
+    pub timestamp_period: Option<u16>,
+    pub modules: Vec<MpptModule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpptModule {
+    pub id: Option<u16>,
+    pub label: String,
+    /// A
+    pub dc_current: Option<f64>,
+    /// V
+    pub dc_voltage: Option<f64>,
+    /// W
+    pub dc_power: Option<f64>,
+    /// Wh
+    pub dc_energy: Option<f64>,
+    /// Seconds since 2000-01-01 00:00 UTC
+    pub timestamp: Option<u32>,
+    /// °C
+    pub temperature: Option<i16>,
+    pub state: Option<u16>,
+    pub events: Option<u32>,
+}
+
+impl Mppt {
+    const FIXED_LEN: usize = 8;
+    const MODULE_LEN: usize = 20;
+
+    fn decode(b: &Block<'_>) -> Self {
+        let count = b.len().saturating_sub(Self::FIXED_LEN) / Self::MODULE_LEN;
+        let modules = (0..count)
+            .map(|i| {
+                let off = Self::FIXED_LEN + i * Self::MODULE_LEN;
+                MpptModule {
+                    id: b.u16(off),
+                    label: b.string(off + 1, 8),
+                    dc_current: b.scaled_u16(off + 9, 0),
+                    dc_voltage: b.scaled_u16(off + 10, 1),
+                    dc_power: b.scaled_u16(off + 11, 2),
+                    dc_energy: b.scaled_acc32(off + 12, 3),
+                    timestamp: b.u32(off + 14),
+                    temperature: b.i16(off + 16),
+                    state: b.u16(off + 17),
+                    events: b.u32(off + 18),
+                }
+            })
+            .collect();
+
+        Self {
+            events: b.u32(4),
+            timestamp_period: b.u16(7),
+            modules,
+        }
+    }
+}
+
+/// Phase values are ordered A, B, C.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Meter {
+    pub id: u16,
+    /// A
+    pub current: Option<f64>,
+    /// A
+    pub phase_current: [Option<f64>; 3],
+    /// V
+    pub voltage_ln: Option<f64>,
+    /// V
+    pub phase_voltage_ln: [Option<f64>; 3],
+    /// V
+    pub voltage_ll: Option<f64>,
+    /// V, ordered AB, BC, CA
+    pub phase_voltage_ll: [Option<f64>; 3],
+    /// Hz
+    pub frequency: Option<f64>,
+    /// W
+    pub power: Option<f64>,
+    /// W
+    pub phase_power: [Option<f64>; 3],
+    /// VA
+    pub apparent_power: Option<f64>,
+    /// VA
+    pub phase_apparent_power: [Option<f64>; 3],
+    /// var
+    pub reactive_power: Option<f64>,
+    /// var
+    pub phase_reactive_power: [Option<f64>; 3],
+    /// Percent
+    pub power_factor: Option<f64>,
+    /// Percent
+    pub phase_power_factor: [Option<f64>; 3],
+    /// Wh
+    pub energy_exported: Option<f64>,
+    /// Wh
+    pub phase_energy_exported: [Option<f64>; 3],
+    /// Wh
+    pub energy_imported: Option<f64>,
+    /// Wh
+    pub phase_energy_imported: [Option<f64>; 3],
+    /// VAh
+    pub apparent_energy_exported: Option<f64>,
+    /// VAh
+    pub apparent_energy_imported: Option<f64>,
+    /// varh, ordered Q1 to Q4
+    pub reactive_energy: [Option<f64>; 4],
+    pub events: Option<u32>,
+}
+
+impl Meter {
+    fn decode(id: u16, b: &Block<'_>) -> Self {
+        Self {
+            id,
+            current: b.scaled_i16(0, 4),
+            phase_current: [1, 2, 3].map(|off| b.scaled_i16(off, 4)),
+            voltage_ln: b.scaled_i16(5, 13),
+            phase_voltage_ln: [6, 7, 8].map(|off| b.scaled_i16(off, 13)),
+            voltage_ll: b.scaled_i16(9, 13),
+            phase_voltage_ll: [10, 11, 12].map(|off| b.scaled_i16(off, 13)),
+            frequency: b.scaled_i16(14, 15),
+            power: b.scaled_i16(16, 20),
+            phase_power: [17, 18, 19].map(|off| b.scaled_i16(off, 20)),
+            apparent_power: b.scaled_i16(21, 25),
+            phase_apparent_power: [22, 23, 24].map(|off| b.scaled_i16(off, 25)),
+            reactive_power: b.scaled_i16(26, 30),
+            phase_reactive_power: [27, 28, 29].map(|off| b.scaled_i16(off, 30)),
+            power_factor: b.scaled_i16(31, 35),
+            phase_power_factor: [32, 33, 34].map(|off| b.scaled_i16(off, 35)),
+            energy_exported: b.scaled_acc32(36, 52),
+            phase_energy_exported: [38, 40, 42].map(|off| b.scaled_acc32(off, 52)),
+            energy_imported: b.scaled_acc32(44, 52),
+            phase_energy_imported: [46, 48, 50].map(|off| b.scaled_acc32(off, 52)),
+            apparent_energy_exported: b.scaled_acc32(53, 69),
+            apparent_energy_imported: b.scaled_acc32(61, 69),
+            reactive_energy: [70, 78, 86, 94].map(|off| b.scaled_acc32(off, 102)),
+            events: b.u32(103),
+        }
+    }
+}
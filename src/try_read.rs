@@ -2,10 +2,8 @@ use crate::{
     context::RobustContext,
     types::{Coil, Word},
 };
-use std::io;
-use tokio_modbus::{
-    client::Reader, Address, Error as ModbusError, Quantity, Result as ModbusResult,
-};
+use std::borrow::Cow;
+use tokio_modbus::{Address, Quantity, Request, Response, Result as ModbusResult};
 
 pub(crate) trait TryRead {
     type Result;
@@ -43,127 +41,80 @@ pub(crate) struct MultipleRegistersWriteRead<'a> {
 impl TryRead for CoilsRead {
     type Result = Coil;
     async fn try_read(self, robust_ctx: &RobustContext) -> ModbusResult<Vec<Self::Result>> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.read_coils(self.addr, self.cnt).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::ReadCoils(self.addr, self.cnt);
+        Ok(robust_ctx
+            .try_call(request)
+            .await?
+            .map(|response| match response {
+                Response::ReadCoils(mut coils) => {
+                    coils.truncate(self.cnt.into());
+                    coils
+                }
+                _ => unreachable!("call() should reject mismatching responses"),
+            }))
     }
 }
 
 impl TryRead for DiscreteInputsRead {
     type Result = Coil;
     async fn try_read(self, robust_ctx: &RobustContext) -> ModbusResult<Vec<Self::Result>> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.read_discrete_inputs(self.addr, self.cnt).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::ReadDiscreteInputs(self.addr, self.cnt);
+        Ok(robust_ctx
+            .try_call(request)
+            .await?
+            .map(|response| match response {
+                Response::ReadDiscreteInputs(mut coils) => {
+                    coils.truncate(self.cnt.into());
+                    coils
+                }
+                _ => unreachable!("call() should reject mismatching responses"),
+            }))
     }
 }
 
 impl TryRead for HoldingRegistersRead {
     type Result = Word;
     async fn try_read(self, robust_ctx: &RobustContext) -> ModbusResult<Vec<Self::Result>> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.read_holding_registers(self.addr, self.cnt).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::ReadHoldingRegisters(self.addr, self.cnt);
+        Ok(robust_ctx
+            .try_call(request)
+            .await?
+            .map(|response| match response {
+                Response::ReadHoldingRegisters(words) => words,
+                _ => unreachable!("call() should reject mismatching responses"),
+            }))
     }
 }
 
 impl TryRead for InputRegistersRead {
     type Result = Word;
     async fn try_read(self, robust_ctx: &RobustContext) -> ModbusResult<Vec<Self::Result>> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.read_input_registers(self.addr, self.cnt).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::ReadInputRegisters(self.addr, self.cnt);
+        Ok(robust_ctx
+            .try_call(request)
+            .await?
+            .map(|response| match response {
+                Response::ReadInputRegisters(words) => words,
+                _ => unreachable!("call() should reject mismatching responses"),
+            }))
     }
 }
 
 impl<'a> TryRead for MultipleRegistersWriteRead<'a> {
     type Result = Word;
     async fn try_read(self, robust_ctx: &RobustContext) -> ModbusResult<Vec<Self::Result>> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => {
-                    ctx.read_write_multiple_registers(
-                        self.read_addr,
-                        self.read_count,
-                        self.write_addr,
-                        self.write_data,
-                    )
-                    .await
-                }
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::ReadWriteMultipleRegisters(
+            self.read_addr,
+            self.read_count,
+            self.write_addr,
+            Cow::Borrowed(self.write_data),
+        );
+        Ok(robust_ctx
+            .try_call(request)
+            .await?
+            .map(|response| match response {
+                Response::ReadWriteMultipleRegisters(words) => words,
+                _ => unreachable!("call() should reject mismatching responses"),
+            }))
     }
 }
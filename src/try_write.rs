@@ -2,8 +2,8 @@ use crate::{
     context::RobustContext,
     types::{Coil, Word},
 };
-use std::io;
-use tokio_modbus::{prelude::*, Address, Error as ModbusError, Result as ModbusResult};
+use std::borrow::Cow;
+use tokio_modbus::{Address, Request, Result as ModbusResult};
 
 pub(crate) trait TryWrite {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()>;
@@ -37,118 +37,35 @@ pub(crate) struct RegisterMaskedWrite {
 
 impl TryWrite for CoilWrite {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.write_single_coil(self.addr, self.coil).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::WriteSingleCoil(self.addr, self.coil);
+        Ok(robust_ctx.try_call(request).await?.map(|_| ()))
     }
 }
 
 impl TryWrite for RegisterWrite {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.write_single_register(self.addr, self.word).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::WriteSingleRegister(self.addr, self.word);
+        Ok(robust_ctx.try_call(request).await?.map(|_| ()))
     }
 }
 
 impl<'a> TryWrite for MultipleCoilsWrite<'a> {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.write_multiple_coils(self.addr, self.coils).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::WriteMultipleCoils(self.addr, Cow::Borrowed(self.coils));
+        Ok(robust_ctx.try_call(request).await?.map(|_| ()))
     }
 }
 
 impl<'a> TryWrite for MultipleRegistersWrite<'a> {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => ctx.write_multiple_registers(self.addr, self.words).await,
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::WriteMultipleRegisters(self.addr, Cow::Borrowed(self.words));
+        Ok(robust_ctx.try_call(request).await?.map(|_| ()))
     }
 }
 
 impl TryWrite for RegisterMaskedWrite {
     async fn try_write(self, robust_ctx: &RobustContext) -> ModbusResult<()> {
-        let ctx = robust_ctx.ctx.clone();
-        let res = {
-            let mut ctx_guard = ctx.lock().await;
-            let ctx = ctx_guard
-                .as_mut()
-                .map_err(|e| ModbusError::Transport(io::Error::new(e.kind(), e.to_string())));
-
-            match ctx {
-                Ok(ctx) => {
-                    ctx.masked_write_register(self.addr, self.and_mask, self.or_mask)
-                        .await
-                }
-                Err(e) => Err(e),
-            }
-        };
-
-        if res.is_err() {
-            RobustContext::refresh_context(ctx.clone(), &robust_ctx.host, robust_ctx.slave).await;
-        }
-
-        res
+        let request = Request::MaskWriteRegister(self.addr, self.and_mask, self.or_mask);
+        Ok(robust_ctx.try_call(request).await?.map(|_| ()))
     }
 }
@@ -0,0 +1,270 @@
+use crate::{
+    context::RobustContext,
+    hooks::DisconnectCause,
+    types::{Coil, Word},
+};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::{sleep_until, Instant};
+use tokio_modbus::{prelude::*, Address};
+use tracing::warn;
+
+/// A write that is held while the device is unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuedWrite {
+    Coil(Address, Coil),
+    Register(Address, Word),
+}
+
+impl QueuedWrite {
+    fn request(&self) -> Request<'static> {
+        match *self {
+            QueuedWrite::Coil(addr, coil) => Request::WriteSingleCoil(addr, coil),
+            QueuedWrite::Register(addr, word) => Request::WriteSingleRegister(addr, word),
+        }
+    }
+
+    /// Whether `other` overwrites the value written by `self`.
+    fn same_target(&self, other: &QueuedWrite) -> bool {
+        match (self, other) {
+            (QueuedWrite::Coil(addr, _), QueuedWrite::Coil(other, _))
+            | (QueuedWrite::Register(addr, _), QueuedWrite::Register(other, _)) => addr == other,
+            _ => false,
+        }
+    }
+}
+
+/// Final outcome of a queued write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Written,
+    Exception(ExceptionCode),
+    /// A later write to the same address replaced this one.
+    Superseded,
+    /// The write was not sent before its TTL ran out.
+    Expired,
+    /// The queue was dropped together with the last clone of the context.
+    Dropped,
+}
+
+#[derive(Debug)]
+struct Pending {
+    id: u64,
+    write: QueuedWrite,
+    expires: Instant,
+    done: oneshot::Sender<WriteOutcome>,
+}
+
+impl Pending {
+    fn complete(self, outcome: WriteOutcome) {
+        let _ = self.done.send(outcome);
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    pending: VecDeque<Pending>,
+}
+
+/// Writes waiting to be replayed, oldest first.
+#[derive(Debug, Default)]
+pub struct WriteQueue {
+    state: Mutex<State>,
+}
+
+impl WriteQueue {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().pending.is_empty()
+    }
+
+    fn push(
+        self: &Arc<Self>,
+        write: QueuedWrite,
+        ttl: Duration,
+    ) -> impl Future<Output = WriteOutcome> + Send + 'static {
+        let (done, mut receiver) = oneshot::channel();
+        let expires = Instant::now() + ttl;
+
+        let id = {
+            let mut state = self.state();
+            let (superseded, pending) = std::mem::take(&mut state.pending)
+                .into_iter()
+                .partition::<VecDeque<_>, _>(|pending| pending.write.same_target(&write));
+            state.pending = pending;
+            superseded
+                .into_iter()
+                .for_each(|pending| pending.complete(WriteOutcome::Superseded));
+
+            state.next_id += 1;
+            let id = state.next_id;
+            state.pending.push_back(Pending {
+                id,
+                write,
+                expires,
+                done,
+            });
+            id
+        };
+
+        let queue = Arc::downgrade(self);
+        async move {
+            tokio::select! {
+                outcome = &mut receiver => return outcome.unwrap_or(WriteOutcome::Dropped),
+                _ = sleep_until(expires) => {}
+            }
+            // Only report expiry if the write is not being sent right now.
+            if queue.upgrade().is_some_and(|queue| queue.remove(id)) {
+                return WriteOutcome::Expired;
+            }
+            receiver.await.unwrap_or(WriteOutcome::Dropped)
+        }
+    }
+
+    fn remove(&self, id: u64) -> bool {
+        let mut state = self.state();
+        let len = state.pending.len();
+        state.pending.retain(|pending| pending.id != id);
+        state.pending.len() != len
+    }
+
+    /// Takes the oldest write that is still wanted.
+    fn pop_front(&self) -> Option<Pending> {
+        let mut state = self.state();
+        while let Some(pending) = state.pending.pop_front() {
+            if pending.done.is_closed() {
+                continue;
+            }
+            if pending.expires <= Instant::now() {
+                pending.complete(WriteOutcome::Expired);
+                continue;
+            }
+            return Some(pending);
+        }
+        None
+    }
+
+    fn push_front(&self, pending: Pending) {
+        if pending.expires <= Instant::now() {
+            pending.complete(WriteOutcome::Expired);
+            return;
+        }
+        self.state().pending.push_front(pending);
+    }
+}
+
+impl RobustContext {
+    /// Holds writes submitted through [`RobustContext::write_queued`] until they can be
+    /// sent. The queue is shared by all clones of this context, including those made
+    /// before it was enabled.
+    pub fn enable_write_queue(&self) -> Arc<WriteQueue> {
+        self.write_queue.get_or_init(Arc::default).clone()
+    }
+
+    /// Writes `write` now, or after the next successful [`RobustContext::reconnect`]
+    /// if the device is unreachable. Queued writes to the same address are coalesced to
+    /// the latest value and replayed in the order they were submitted.
+    ///
+    /// The returned future resolves with the final outcome, at the latest when `ttl` ran
+    /// out.
+    pub async fn write_queued(
+        &mut self,
+        write: QueuedWrite,
+        ttl: Duration,
+    ) -> impl Future<Output = WriteOutcome> + Send + 'static {
+        let completion = self.enable_write_queue().push(write, ttl);
+
+        if self.is_connected() {
+            self.replay_writes().await;
+        }
+        if !self.is_connected() {
+            self.reconnect().await;
+        }
+
+        completion
+    }
+
+    /// Sends queued writes until the queue is empty or the connection fails again.
+    pub(crate) async fn replay_writes(&self) {
+        let Some(queue) = self.write_queue.get() else {
+            return;
+        };
+
+        while let Some(pending) = queue.pop_front() {
+            match self.send(pending.write.request()).await {
+                Ok(Ok(_)) => {
+                    if let Some(cache) = self.cache.get() {
+                        match pending.write {
+                            QueuedWrite::Coil(addr, coil) => cache.store_coils(addr, &[coil]),
+                            QueuedWrite::Register(addr, word) => {
+                                cache.store_holding_registers(addr, &[word])
+                            }
+                        }
+                    }
+                    pending.complete(WriteOutcome::Written);
+                }
+                Ok(Err(e)) => pending.complete(WriteOutcome::Exception(e)),
+                Err(e) => {
+                    warn!("could not replay queued write {:?}: {}", pending.write, e);
+                    queue.push_front(pending);
+                    self.mark_disconnected(DisconnectCause::transport(&e)).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn later_write_supersedes() {
+        let queue = Arc::new(WriteQueue::default());
+        let first = queue.push(QueuedWrite::Register(1, 10), Duration::from_secs(1));
+        let other = queue.push(QueuedWrite::Coil(1, true), Duration::from_secs(1));
+        let _second = queue.push(QueuedWrite::Register(1, 20), Duration::from_secs(1));
+
+        assert_eq!(first.await, WriteOutcome::Superseded);
+        assert_eq!(queue.len(), 2);
+        let pending = queue.pop_front().unwrap();
+        assert_eq!(pending.write, QueuedWrite::Coil(1, true));
+        pending.complete(WriteOutcome::Written);
+        assert_eq!(other.await, WriteOutcome::Written);
+        assert_eq!(
+            queue.pop_front().unwrap().write,
+            QueuedWrite::Register(1, 20)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expires_after_ttl() {
+        let queue = Arc::new(WriteQueue::default());
+        let write = queue.push(QueuedWrite::Register(1, 10), Duration::from_secs(1));
+        assert_eq!(write.await, WriteOutcome::Expired);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn enabled_for_earlier_clones() {
+        let ctx = RobustContext::new("127.0.0.1:502", Slave(1)).await.unwrap();
+        let clone = ctx.clone();
+        let queue = ctx.enable_write_queue();
+        assert!(clone
+            .write_queue
+            .get()
+            .is_some_and(|shared| Arc::ptr_eq(shared, &queue)));
+    }
+}
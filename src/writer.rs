@@ -1,18 +1,18 @@
 use crate::{
+    chunk::slices,
     context::RobustContext,
     try_write::{
         CoilWrite, MultipleCoilsWrite, MultipleRegistersWrite, RegisterMaskedWrite, RegisterWrite,
         TryWrite,
     },
 };
-use tokio_modbus::{prelude::*, Address, Result as ModbusResult};
-use tokio_retry::Retry;
+use tokio_modbus::{prelude::*, Address, FunctionCode, Result as ModbusResult};
+use tracing::Instrument;
 
 use crate::types::{Coil, Word};
 
 impl Writer for RobustContext {
     #[doc = " Write a single coil (0x05)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn write_single_coil<'life0, 'async_trait>(
         &'life0 mut self,
@@ -29,14 +29,21 @@ impl Writer for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { CoilWrite { addr, coil }.try_write(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::WriteSingleCoil, addr, 1);
+        Box::pin(
+            async move {
+                let action = || async { CoilWrite { addr, coil }.try_write(self).await };
+                let res = self.retry(action).await;
+                if let (Some(cache), Ok(Ok(_))) = (self.cache.get(), &res) {
+                    cache.store_coils(addr, &[coil]);
+                }
+                res
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Write a single holding register (0x06)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn write_single_register<'life0, 'async_trait>(
         &'life0 mut self,
@@ -53,14 +60,21 @@ impl Writer for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { RegisterWrite { addr, word }.try_write(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::WriteSingleRegister, addr, 1);
+        Box::pin(
+            async move {
+                let action = || async { RegisterWrite { addr, word }.try_write(self).await };
+                let res = self.retry(action).await;
+                if let (Some(cache), Ok(Ok(_))) = (self.cache.get(), &res) {
+                    cache.store_holding_registers(addr, &[word]);
+                }
+                res
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Write multiple coils (0x0F)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn write_multiple_coils<'life0, 'life1, 'async_trait>(
         &'life0 mut self,
@@ -78,14 +92,26 @@ impl Writer for RobustContext {
         'life1: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { MultipleCoilsWrite { addr, coils }.try_write(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::WriteMultipleCoils, addr, coils.len());
+        Box::pin(
+            async move {
+                for (addr, coils) in slices(addr, coils, self.limits.write_coils)? {
+                    let action =
+                        || async { MultipleCoilsWrite { addr, coils }.try_write(self).await };
+                    if let Err(e) = self.retry(action).await? {
+                        return Ok(Err(e));
+                    }
+                    if let Some(cache) = self.cache.get() {
+                        cache.store_coils(addr, coils);
+                    }
+                }
+                Ok(Ok(()))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Write multiple holding registers (0x10)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn write_multiple_registers<'life0, 'life1, 'async_trait>(
         &'life0 mut self,
@@ -103,14 +129,26 @@ impl Writer for RobustContext {
         'life1: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async { MultipleRegistersWrite { addr, words }.try_write(self).await };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+        let span = self.request_span(FunctionCode::WriteMultipleRegisters, addr, words.len());
+        Box::pin(
+            async move {
+                for (addr, words) in slices(addr, words, self.limits.write_registers)? {
+                    let action =
+                        || async { MultipleRegistersWrite { addr, words }.try_write(self).await };
+                    if let Err(e) = self.retry(action).await? {
+                        return Ok(Err(e));
+                    }
+                    if let Some(cache) = self.cache.get() {
+                        cache.store_holding_registers(addr, words);
+                    }
+                }
+                Ok(Ok(()))
+            }
+            .instrument(span),
+        )
     }
 
     #[doc = " Set or clear individual bits of a holding register (0x16)"]
-    #[must_use]
     #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
     fn masked_write_register<'life0, 'async_trait>(
         &'life0 mut self,
@@ -128,17 +166,25 @@ impl Writer for RobustContext {
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        Box::pin(async move {
-            let action = || async {
-                RegisterMaskedWrite {
-                    addr,
-                    and_mask,
-                    or_mask,
+        let span = self.request_span(FunctionCode::MaskWriteRegister, addr, 1);
+        Box::pin(
+            async move {
+                let action = || async {
+                    RegisterMaskedWrite {
+                        addr,
+                        and_mask,
+                        or_mask,
+                    }
+                    .try_write(self)
+                    .await
+                };
+                let res = self.retry(action).await;
+                if let Some(cache) = self.cache.get() {
+                    cache.invalidate_holding_register(addr);
                 }
-                .try_write(self)
-                .await
-            };
-            Retry::spawn(RobustContext::retry_strategy_command(), action).await
-        })
+                res
+            }
+            .instrument(span),
+        )
     }
 }